@@ -0,0 +1,127 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use parallel_hashlife_rust::hashtable_base::{
+    backoff_count, HashTable, Key, SyncTableEntry, TableEntry, TableEntryValues,
+};
+use rustc_hash::FxBuildHasher;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::sync::Barrier;
+use std::thread;
+
+type Entry = SyncTableEntry<(), NonZeroU32>;
+
+const LOG2_CAPACITY: u32 = 20;
+
+/// number of keys each thread inserts
+const KEYS_PER_THREAD: u32 = 1 << 16;
+
+const THREAD_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+#[derive(Copy, Clone, Debug)]
+enum KeyMix {
+    /// each thread inserts its own keys
+    Disjoint,
+    /// all threads insert the same keys, racing to fill the same entries;
+    /// a thread that finds an entry still being filled goes through the
+    /// backoff path in `SyncTableEntry::get`
+    Overlapping,
+    /// half of each thread's keys are shared with every other thread
+    Mixed,
+}
+
+impl KeyMix {
+    const ALL: [KeyMix; 3] = [KeyMix::Disjoint, KeyMix::Overlapping, KeyMix::Mixed];
+    fn name(self) -> &'static str {
+        match self {
+            KeyMix::Disjoint => "disjoint",
+            KeyMix::Overlapping => "overlapping",
+            KeyMix::Mixed => "mixed",
+        }
+    }
+    /// the `index`th key inserted by `thread_index`
+    fn key_index(self, thread_index: u32, index: u32) -> u32 {
+        let own = thread_index * KEYS_PER_THREAD + index;
+        match self {
+            KeyMix::Disjoint => own,
+            KeyMix::Overlapping => index,
+            KeyMix::Mixed if index < KEYS_PER_THREAD / 2 => index,
+            KeyMix::Mixed => own,
+        }
+    }
+}
+
+fn make_key(v: u32) -> Key {
+    let id = |i: u32| NonZeroU32::new((v.wrapping_mul(8) + i) | 1).unwrap();
+    Key([
+        [[id(0), id(1)], [id(2), id(3)]],
+        [[id(4), id(5)], [id(6), id(7)]],
+    ])
+}
+
+fn new_table() -> Arc<HashTable<Entry, FxBuildHasher>> {
+    Arc::new(HashTable::with_search_limit(1 << LOG2_CAPACITY, usize::MAX))
+}
+
+/// all threads wait on a barrier before inserting, so they contend from the
+/// start instead of the first thread finishing before the last one spawns
+fn insert_from_threads(
+    table: Arc<HashTable<Entry, FxBuildHasher>>,
+    thread_count: u32,
+    key_mix: KeyMix,
+) -> Arc<HashTable<Entry, FxBuildHasher>> {
+    let barrier = Arc::new(Barrier::new(thread_count as usize));
+    let threads: Vec<_> = (0..thread_count)
+        .map(|thread_index| {
+            let table = table.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                for index in 0..KEYS_PER_THREAD {
+                    let key = make_key(key_mix.key_index(thread_index, index));
+                    let values = <Entry as TableEntry>::Values::new((), None);
+                    assert!(table.get_or_insert(key, values).is_ok());
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    table
+}
+
+fn bench_concurrent_inserts(c: &mut Criterion) {
+    for &key_mix in &KeyMix::ALL {
+        let mut group = c.benchmark_group(format!("concurrent/{}", key_mix.name()));
+        for &thread_count in &THREAD_COUNTS {
+            // criterion only measures time, so count backoffs in a separate
+            // run; needs the `stats` feature, otherwise this reports 0
+            let backoffs_before = backoff_count();
+            insert_from_threads(new_table(), thread_count, key_mix);
+            println!(
+                "concurrent/{}/{}: {} backoffs per run",
+                key_mix.name(),
+                thread_count,
+                backoff_count() - backoffs_before
+            );
+            group.throughput(Throughput::Elements(u64::from(
+                thread_count * KEYS_PER_THREAD,
+            )));
+            group.bench_with_input(
+                BenchmarkId::from_parameter(thread_count),
+                &thread_count,
+                |b, &thread_count| {
+                    b.iter_batched(
+                        new_table,
+                        |table| insert_from_threads(table, thread_count, key_mix),
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_concurrent_inserts);
+criterion_main!(benches);
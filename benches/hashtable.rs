@@ -0,0 +1,269 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use parallel_hashlife_rust::hashtable_base::{
+    CachedHashTable, HashTable, Key, LinearProbeSequence, LocalTableEntry, ProbeSequence,
+    QuadraticProbeSequence, RobinHoodHashTable, SyncTableEntry, TableEntry, TableEntryValues,
+};
+use std::hash::BuildHasher;
+use std::hash::BuildHasherDefault;
+use std::hash::Hasher;
+use std::num::NonZeroU32;
+
+const LOAD_FACTORS: [(u32, u32); 4] = [(1, 4), (1, 2), (3, 4), (9, 10)];
+
+/// spans tables that fit in L1 cache up to tables that only fit in main memory
+const LOG2_CAPACITIES: [u32; 4] = [8, 12, 16, 22];
+
+const SEED: u64 = 0x1234_5678_9ABC_DEF0;
+
+/// seed for keys that are never inserted, for lookups that miss
+const MISS_SEED: u64 = 0x0FED_CBA9_8765_4321;
+
+/// the root and a handful of high-level nodes, which a step looks up constantly
+const HOT_KEY_COUNT: usize = 4;
+
+/// lookups per iteration of `bench_cached`
+const HOT_LOOKUP_COUNT: usize = 1 << 16;
+
+/// deterministic hasher so runs are comparable, `RandomState` is seeded per-process
+#[derive(Default)]
+struct SplitMixHasher(u64);
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl Hasher for SplitMixHasher {
+    fn finish(&self) -> u64 {
+        let mut state = self.0;
+        splitmix64(&mut state)
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(byte);
+        }
+    }
+    fn write_u32(&mut self, v: u32) {
+        self.0 = splitmix64(&mut (self.0 ^ u64::from(v)));
+    }
+}
+
+type DeterministicBuildHasher = BuildHasherDefault<SplitMixHasher>;
+
+fn make_keys(count: usize) -> Vec<Key> {
+    make_keys_with_seed(count, SEED)
+}
+
+fn make_keys_with_seed(count: usize, seed: u64) -> Vec<Key> {
+    let mut state = seed;
+    let mut next_id = || NonZeroU32::new(splitmix64(&mut state) as u32 | 1).unwrap();
+    (0..count)
+        .map(|_| {
+            Key([
+                [[next_id(), next_id()], [next_id(), next_id()]],
+                [[next_id(), next_id()], [next_id(), next_id()]],
+            ])
+        })
+        .collect()
+}
+
+/// `HOT_LOOKUP_COUNT` lookups of `keys`, 3 in 4 of which are of one of the
+/// first `HOT_KEY_COUNT` keys and the rest spread over all of them
+fn hot_key_lookups(keys: &[Key]) -> Vec<Key> {
+    let mut state = SEED;
+    (0..HOT_LOOKUP_COUNT)
+        .map(|_| {
+            let random = splitmix64(&mut state);
+            let index = (random >> 8) as usize;
+            if random & 3 == 0 {
+                keys[index % keys.len()]
+            } else {
+                keys[index % HOT_KEY_COUNT]
+            }
+        })
+        .collect()
+}
+
+fn filled_table<Entry: TableEntry, BH: BuildHasher + Default, P: ProbeSequence>(
+    capacity: usize,
+    keys: &[Key],
+) -> HashTable<Entry, BH, P>
+where
+    Entry::Values: TableEntryValues<EarlyValue = (), LateValue = NonZeroU32>,
+{
+    let table = HashTable::with_search_limit(capacity, usize::MAX);
+    for &key in keys {
+        assert!(table.insert(key, Entry::Values::new((), None)).is_ok());
+    }
+    table
+}
+
+fn bench_backend<Entry: TableEntry, P: ProbeSequence>(c: &mut Criterion, backend: &str)
+where
+    Entry::Values: TableEntryValues<EarlyValue = (), LateValue = NonZeroU32>,
+{
+    for &log2_capacity in &LOG2_CAPACITIES {
+        let capacity = 1usize << log2_capacity;
+        let mut insert_group = c.benchmark_group(format!("{}/insert/2^{}", backend, log2_capacity));
+        for &(numerator, denominator) in &LOAD_FACTORS {
+            let keys = make_keys(capacity * numerator as usize / denominator as usize);
+            let load_factor = format!("{}/{}", numerator, denominator);
+            insert_group.throughput(Throughput::Elements(keys.len() as u64));
+            insert_group.bench_with_input(
+                BenchmarkId::from_parameter(&load_factor),
+                &keys,
+                |b, keys| {
+                    b.iter_batched(
+                        || {
+                            HashTable::<Entry, DeterministicBuildHasher, P>::with_search_limit(
+                                capacity,
+                                usize::MAX,
+                            )
+                        },
+                        |table| {
+                            for &key in keys {
+                                assert!(table.insert(key, Entry::Values::new((), None)).is_ok());
+                            }
+                            table
+                        },
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+        insert_group.finish();
+        let mut find_group = c.benchmark_group(format!("{}/find/2^{}", backend, log2_capacity));
+        for &(numerator, denominator) in &LOAD_FACTORS {
+            let keys = make_keys(capacity * numerator as usize / denominator as usize);
+            let load_factor = format!("{}/{}", numerator, denominator);
+            let table = filled_table::<Entry, DeterministicBuildHasher, P>(capacity, &keys);
+            find_group.throughput(Throughput::Elements(keys.len() as u64));
+            find_group.bench_with_input(
+                BenchmarkId::from_parameter(&load_factor),
+                &keys,
+                |b, keys| {
+                    b.iter(|| {
+                        for &key in keys {
+                            assert!(table.find(key).is_some());
+                        }
+                    })
+                },
+            );
+        }
+        find_group.finish();
+        let mut find_miss_group =
+            c.benchmark_group(format!("{}/find_miss/2^{}", backend, log2_capacity));
+        for &(numerator, denominator) in &LOAD_FACTORS {
+            let len = capacity * numerator as usize / denominator as usize;
+            let load_factor = format!("{}/{}", numerator, denominator);
+            let table =
+                filled_table::<Entry, DeterministicBuildHasher, P>(capacity, &make_keys(len));
+            let missing_keys = make_keys_with_seed(len, MISS_SEED);
+            find_miss_group.throughput(Throughput::Elements(missing_keys.len() as u64));
+            find_miss_group.bench_with_input(
+                BenchmarkId::from_parameter(&load_factor),
+                &missing_keys,
+                |b, missing_keys| {
+                    b.iter(|| {
+                        for &key in missing_keys {
+                            assert!(table.find(key).is_none());
+                        }
+                    })
+                },
+            );
+        }
+        find_miss_group.finish();
+    }
+}
+
+fn bench_local(c: &mut Criterion) {
+    bench_backend::<LocalTableEntry<(), NonZeroU32>, LinearProbeSequence>(c, "local");
+}
+
+fn bench_local_quadratic(c: &mut Criterion) {
+    bench_backend::<LocalTableEntry<(), NonZeroU32>, QuadraticProbeSequence>(c, "local_quadratic");
+}
+
+fn bench_sync(c: &mut Criterion) {
+    bench_backend::<SyncTableEntry<(), NonZeroU32>, LinearProbeSequence>(c, "sync");
+}
+
+fn bench_robin_hood(c: &mut Criterion) {
+    for &log2_capacity in &LOG2_CAPACITIES {
+        let capacity = 1usize << log2_capacity;
+        let mut find_group = c.benchmark_group(format!("robin_hood/find/2^{}", log2_capacity));
+        for &(numerator, denominator) in &LOAD_FACTORS {
+            let keys = make_keys(capacity * numerator as usize / denominator as usize);
+            let load_factor = format!("{}/{}", numerator, denominator);
+            let table = RobinHoodHashTable::<(), DeterministicBuildHasher>::new(capacity);
+            for &key in &keys {
+                assert!(table.insert(key, ()).is_ok());
+            }
+            find_group.throughput(Throughput::Elements(keys.len() as u64));
+            find_group.bench_with_input(
+                BenchmarkId::from_parameter(&load_factor),
+                &keys,
+                |b, keys| {
+                    b.iter(|| {
+                        for &key in keys {
+                            assert!(table.find(key).is_some());
+                        }
+                    })
+                },
+            );
+        }
+        find_group.finish();
+    }
+}
+
+/// `CachedHashTable::find` against a plain `find` of the same table when a
+/// few keys get most of the lookups
+fn bench_cached(c: &mut Criterion) {
+    type Entry = LocalTableEntry<(), NonZeroU32>;
+    let capacity = 1 << 16;
+    let keys = make_keys(capacity / 2);
+    let lookups = hot_key_lookups(&keys);
+    let table = CachedHashTable::new(filled_table::<
+        Entry,
+        DeterministicBuildHasher,
+        LinearProbeSequence,
+    >(capacity, &keys));
+    // criterion only measures time, so report the hit rate from a separate run
+    for &key in &lookups {
+        assert!(table.find(key).is_some());
+    }
+    println!(
+        "cached/hot_keys: {:.1}% of lookups hit the cache",
+        100.0 * table.hit_count() as f64 / lookups.len() as f64
+    );
+    let mut group = c.benchmark_group("cached/hot_keys");
+    group.throughput(Throughput::Elements(lookups.len() as u64));
+    group.bench_function("find", |b| {
+        b.iter(|| {
+            for &key in &lookups {
+                assert!(table.table().find(key).is_some());
+            }
+        })
+    });
+    group.bench_function("cached_find", |b| {
+        b.iter(|| {
+            for &key in &lookups {
+                assert!(table.find(key).is_some());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_local,
+    bench_local_quadratic,
+    bench_sync,
+    bench_robin_hood,
+    bench_cached
+);
+criterion_main!(benches);
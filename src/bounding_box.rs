@@ -0,0 +1,96 @@
+/// the lowest level `Step` can step: `Step<Level1>` steps a `Key<Level2>`
+pub const MIN_STEP_LEVEL: usize = 2;
+
+/// an axis-aligned box of cells, indexed `[x, y, z]`; both `min` and `max`
+/// are inclusive, so a box is never empty
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BoundingBox {
+    pub min: [i64; 3],
+    pub max: [i64; 3],
+}
+
+impl BoundingBox {
+    /// panics if `min` is greater than `max` along any axis
+    pub fn new(min: [i64; 3], max: [i64; 3]) -> Self {
+        assert!(
+            (0..3).all(|axis| min[axis] <= max[axis]),
+            "min must not be greater than max"
+        );
+        BoundingBox { min, max }
+    }
+    /// the box holding just `position`
+    pub fn cell(position: [i64; 3]) -> Self {
+        BoundingBox {
+            min: position,
+            max: position,
+        }
+    }
+    pub fn contains(&self, position: [i64; 3]) -> bool {
+        (0..3).all(|axis| self.min[axis] <= position[axis] && position[axis] <= self.max[axis])
+    }
+    /// the smallest level a root node needs for this box to survive a step.
+    ///
+    /// A node in the table for level `L` is `2^(L + 1)` cells wide (a leaf
+    /// key is a 2x2x2 block of cells). A root at level `L` is centered on the
+    /// origin, covering `-2^L..2^L` along each axis, and stepping it gives
+    /// back only its inner half, `-2^(L - 1)..2^(L - 1)`, so the box has to
+    /// fit in that. Never less than `MIN_STEP_LEVEL`.
+    pub fn enclosing_level(&self) -> usize {
+        // the inner half must reach `max + 1` above the origin and `-min`
+        // below it; i128 so neither overflows for extreme coordinates
+        let radius = (0..3)
+            .map(|axis| (i128::from(self.max[axis]) + 1).max(-i128::from(self.min[axis])))
+            .max()
+            .unwrap();
+        // `radius` is at least 1 since `min <= max`, and at most `2^63`
+        let half_width_log2 = (radius as u128).next_power_of_two().trailing_zeros() as usize;
+        (half_width_log2 + 1).max(MIN_STEP_LEVEL)
+    }
+    /// the cells in the box, in `[x][y][z]` order with `z` varying fastest
+    pub fn cells(&self) -> impl Iterator<Item = [i64; 3]> {
+        let BoundingBox { min, max } = *self;
+        (min[0]..=max[0]).flat_map(move |x| {
+            (min[1]..=max[1]).flat_map(move |y| (min[2]..=max[2]).map(move |z| [x, y, z]))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cells() {
+        let bounding_box = BoundingBox::new([0, -1, 5], [1, -1, 6]);
+        let cells: Vec<_> = bounding_box.cells().collect();
+        assert_eq!(cells, vec![[0, -1, 5], [0, -1, 6], [1, -1, 5], [1, -1, 6]]);
+        assert!(cells.iter().all(|&cell| bounding_box.contains(cell)));
+        assert!(!bounding_box.contains([0, 0, 5]));
+        assert_eq!(BoundingBox::cell([3, 4, 5]).cells().count(), 1);
+    }
+
+    #[test]
+    fn test_enclosing_level() {
+        let level = |min, max| BoundingBox::new(min, max).enclosing_level();
+        // a level 2 root is 8 cells wide and steps to its inner -2..2
+        assert_eq!(level([0; 3], [0; 3]), MIN_STEP_LEVEL);
+        assert_eq!(level([-2; 3], [1; 3]), 2);
+        assert_eq!(level([-2, 0, 0], [2, 0, 0]), 3);
+        assert_eq!(level([-3, 0, 0], [1, 0, 0]), 3);
+        // exact power of two boundaries of the inner half, -2^(L-1)..2^(L-1)
+        for l in 3..62 {
+            let half = 1i64 << (l - 1);
+            assert_eq!(level([-half, 0, 0], [half - 1, 0, 0]), l);
+            assert_eq!(level([0, 0, 0], [0, 0, half]), l + 1);
+            assert_eq!(level([0, -half - 1, 0], [0, 0, 0]), l + 1);
+            assert_eq!(level([0, 0, 0], [0, half / 2, 0]), l);
+        }
+        assert_eq!(level([i64::MIN; 3], [i64::MAX; 3]), 64);
+    }
+
+    #[test]
+    #[should_panic(expected = "min must not be greater than max")]
+    fn test_new_inverted() {
+        BoundingBox::new([0, 1, 0], [0, 0, 0]);
+    }
+}
@@ -1,22 +1,97 @@
+//! the recursive hashlife step, with a `local` (single-threaded) and a
+//! `sync` (parallel) backend.
+//!
+//! The sync backend's `Step` requires `HashTables<Entry, BH>: Sync`:
+//!
+//! ```
+//! # use parallel_hashlife_rust::hashtable::{HashTables, Key, Level2};
+//! # use parallel_hashlife_rust::hashtable_base::SyncTableEntry;
+//! # use parallel_hashlife_rust::hashlife::sync::Step;
+//! # use parallel_hashlife_rust::rules::Conway3D;
+//! # use rustc_hash::FxBuildHasher;
+//! # use std::num::NonZeroU32;
+//! type Entry = SyncTableEntry<(), NonZeroU32>;
+//! fn step(hash_tables: &HashTables<Entry, FxBuildHasher>, key: Key<Level2>) {
+//!     let _ = Conway3D.step(hash_tables, key);
+//! }
+//! ```
+//!
+//! `LocalTableEntry` isn't `Sync`, so the sync backend can't step it:
+//!
+//! ```compile_fail,E0277
+//! # use parallel_hashlife_rust::hashtable::{HashTables, Key, Level2};
+//! # use parallel_hashlife_rust::hashtable_base::LocalTableEntry;
+//! # use parallel_hashlife_rust::hashlife::sync::Step;
+//! # use parallel_hashlife_rust::rules::Conway3D;
+//! # use rustc_hash::FxBuildHasher;
+//! # use std::num::NonZeroU32;
+//! type Entry = LocalTableEntry<(), NonZeroU32>;
+//! fn step(hash_tables: &HashTables<Entry, FxBuildHasher>, key: Key<Level2>) {
+//!     let _ = Conway3D.step(hash_tables, key);
+//! }
+//! ```
+//!
+//! The local backend's `MaybeSync` holds for every type, so it can:
+//!
+//! ```
+//! # use parallel_hashlife_rust::hashtable::{HashTables, Key, Level2};
+//! # use parallel_hashlife_rust::hashtable_base::LocalTableEntry;
+//! # use parallel_hashlife_rust::hashlife::local::Step;
+//! # use parallel_hashlife_rust::rules::Conway3D;
+//! # use rustc_hash::FxBuildHasher;
+//! # use std::num::NonZeroU32;
+//! type Entry = LocalTableEntry<(), NonZeroU32>;
+//! fn step(hash_tables: &HashTables<Entry, FxBuildHasher>, key: Key<Level2>) {
+//!     let _ = Conway3D.step(hash_tables, key);
+//! }
+//! ```
+
+// the step is still a sketch that doesn't use its inputs yet
+#![allow(unused_variables)]
+
 use crate::hashtable::{
-    GetOrInsertFailureReason, HashTables, Id, Key, Leaf, Level, Level1, Level2, NonLeaf,
-    NonLeafLevel, TableEntry,
+    FailureReason, HashTables, Id, Key, Leaf, Level1, Level2, NonLeaf, NonLeafLevel, TableEntry,
 };
+use crate::rules::Conway3D;
 use std::hash::BuildHasher;
 
-#[derive(Debug)]
-pub enum FailureReason {
-    TableFullOrSearchLimitHit,
+/// arranges the children of each of a node's children into one 4x4x4 grid.
+/// Everything is indexed `[x][y][z]`, like `Key::children`.
+#[allow(clippy::type_complexity)]
+pub fn grandchildren_grid<T: Copy>(children: [[[[[[T; 2]; 2]; 2]; 2]; 2]; 2]) -> [[[T; 4]; 4]; 4] {
+    let mut grid = [[[children[0][0][0][0][0][0]; 4]; 4]; 4];
+    for (x, plane) in grid.iter_mut().enumerate() {
+        for (y, row) in plane.iter_mut().enumerate() {
+            for (z, cell) in row.iter_mut().enumerate() {
+                *cell = children[x / 2][y / 2][z / 2][x % 2][y % 2][z % 2];
+            }
+        }
+    }
+    grid
 }
 
-impl<T> From<GetOrInsertFailureReason<T>> for FailureReason {
-    fn from(v: GetOrInsertFailureReason<T>) -> Self {
-        match v {
-            GetOrInsertFailureReason::TableFullOrSearchLimitHit { .. } => {
-                FailureReason::TableFullOrSearchLimitHit
+/// the 3x3x3 part of a node's `grandchildren_grid` that's needed to compute
+/// its output octant `(ox, oy, oz)`.
+///
+/// The output of a step is the center 2x2x2 of the grid, so output octant
+/// `o` is centered on grid position `o + 1` along each axis and the window
+/// covers positions `o..o + 3`, spilling over into the neighboring children.
+pub fn gather_3x3x3<T: Copy>(
+    grid: &[[[T; 4]; 4]; 4],
+    ox: usize,
+    oy: usize,
+    oz: usize,
+) -> [[[T; 3]; 3]; 3] {
+    assert!(ox < 2 && oy < 2 && oz < 2, "invalid output octant");
+    let mut retval = [[[grid[0][0][0]; 3]; 3]; 3];
+    for (dx, plane) in retval.iter_mut().enumerate() {
+        for (dy, row) in plane.iter_mut().enumerate() {
+            for (dz, cell) in row.iter_mut().enumerate() {
+                *cell = grid[ox + dx][oy + dy][oz + dz];
             }
         }
     }
+    retval
 }
 
 macro_rules! parallel_for_2 {
@@ -35,19 +110,35 @@ macro_rules! parallel_for_2 {
 }
 
 macro_rules! impl_hashlife {
-    ($send:ident, $sync:ident, $mod:ident, $join:path) => {
+    ($maybe_sync:ident, $mod:ident, $join:path) => {
         pub mod $mod {
             use super::*;
+            use crate::$maybe_sync::MaybeSync;
             use $join as join;
 
-            pub trait StepBase: $sync {
+            pub trait StepBase: MaybeSync {
                 fn get_next_state(
                     &self,
                     state: [[[Id<Leaf>; 3]; 3]; 3],
                 ) -> Result<Id<Leaf>, FailureReason>;
             }
 
-            pub trait Step<L: NonLeafLevel, Entry: TableEntry, BH: BuildHasher>: StepBase {
+            impl StepBase for Conway3D {
+                fn get_next_state(
+                    &self,
+                    state: [[[Id<Leaf>; 3]; 3]; 3],
+                ) -> Result<Id<Leaf>, FailureReason> {
+                    Ok(Conway3D::next_state(state))
+                }
+            }
+
+            /// the sync backend requires `HashTables<Entry, BH>: Sync` up front,
+            /// so a table that can't be shared with the threads `join` runs on
+            /// is rejected here rather than deep inside the recursion.
+            pub trait Step<L: NonLeafLevel, Entry: TableEntry, BH: BuildHasher>: StepBase
+            where
+                HashTables<Entry, BH>: MaybeSync,
+            {
                 fn step(
                     &self,
                     hashtables: &HashTables<Entry, BH>,
@@ -55,7 +146,10 @@ macro_rules! impl_hashlife {
                 ) -> Result<Key<L>, FailureReason>;
             }
 
-            impl<T: StepBase, Entry: TableEntry, BH: BuildHasher> Step<Level1, Entry, BH> for T {
+            impl<T: StepBase, Entry: TableEntry, BH: BuildHasher> Step<Level1, Entry, BH> for T
+            where
+                HashTables<Entry, BH>: MaybeSync,
+            {
                 fn step(
                     &self,
                     hashtables: &HashTables<Entry, BH>,
@@ -72,11 +166,14 @@ macro_rules! impl_hashlife {
                             }
                         }
                     }
+                    todo!()
                 }
             }
 
             impl<T: StepBase, L: NonLeafLevel, Entry: TableEntry, BH: BuildHasher>
                 Step<NonLeaf<L>, Entry, BH> for T
+            where
+                HashTables<Entry, BH>: MaybeSync,
             {
                 fn step(
                     &self,
@@ -101,34 +198,62 @@ macro_rules! impl_hashlife {
     };
 }
 
-pub trait FakeSend {}
-
-impl<T: ?Sized> FakeSend for T {}
-
-pub trait FakeSync {}
-
-impl<T: ?Sized> FakeSync for T {}
-
 fn sync_join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
 where
-    A: FnOnce() -> RA + Send,
-    B: FnOnce() -> RB + Send,
-    RA: Send,
-    RB: Send,
+    A: FnOnce() -> RA + crate::sync::MaybeSend,
+    B: FnOnce() -> RB + crate::sync::MaybeSend,
+    RA: crate::sync::MaybeSend,
+    RB: crate::sync::MaybeSend,
 {
     todo!()
 }
 
 fn local_join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
 where
-    A: FnOnce() -> RA + FakeSend,
-    B: FnOnce() -> RB + FakeSend,
-    RA: FakeSend,
-    RB: FakeSend,
+    A: FnOnce() -> RA + crate::unsync::MaybeSend,
+    B: FnOnce() -> RB + crate::unsync::MaybeSend,
+    RA: crate::unsync::MaybeSend,
+    RB: crate::unsync::MaybeSend,
 {
     let ra = a();
     (ra, b())
 }
 
-impl_hashlife!(FakeSend, FakeSync, local, local_join);
-impl_hashlife!(Send, Sync, sync, sync_join);
+impl_hashlife!(unsync, local, local_join);
+impl_hashlife!(sync, sync, sync_join);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gather_3x3x3() {
+        // every grandchild is labeled with its position in the 4x4x4 grid as
+        // the three digits `xyz`
+        let label = |x: usize, y: usize, z: usize| x * 100 + y * 10 + z;
+        let mut children = [[[[[[0; 2]; 2]; 2]; 2]; 2]; 2];
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    children[x / 2][y / 2][z / 2][x % 2][y % 2][z % 2] = label(x, y, z);
+                }
+            }
+        }
+        let grid = grandchildren_grid(children);
+        assert_eq!(grid[0][0][0], 0);
+        assert_eq!(grid[1][2][3], 123);
+        assert_eq!(grid[3][0][2], 302);
+        // worked by hand: octant (1, 0, 1) is the window x in 1..=3,
+        // y in 0..=2, z in 1..=3, which takes cells from all 8 children
+        let window = gather_3x3x3(&grid, 1, 0, 1);
+        assert_eq!(window[0][0], [101, 102, 103]);
+        assert_eq!(window[0][2], [121, 122, 123]);
+        assert_eq!(window[1][1], [211, 212, 213]);
+        assert_eq!(window[2][0], [301, 302, 303]);
+        assert_eq!(window[2][2], [321, 322, 323]);
+        // the center of the window is the grandchild the octant is centered on
+        assert_eq!(gather_3x3x3(&grid, 0, 0, 0)[1][1][1], 111);
+        assert_eq!(gather_3x3x3(&grid, 1, 1, 1)[1][1][1], 222);
+        assert_eq!(gather_3x3x3(&grid, 1, 1, 0)[1][1][1], 221);
+    }
+}
@@ -1,20 +1,259 @@
 pub use crate::hashtable_base::GetOrInsertFailureReason;
 pub use crate::hashtable_base::GetOrInsertSuccess;
 pub use crate::hashtable_base::InsertFailureReason;
+pub use crate::hashtable_base::Ref;
 pub use crate::hashtable_base::TableEntry;
 use crate::hashtable_base::{
-    HashTable as BaseHashTable, Key as BaseKey, TableEntryValues as TableEntryValuesBase,
+    HashTable as BaseHashTable, Key as BaseKey, ProbeSequence, RobinHoodHashTable,
+    TableEntryValues as TableEntryValuesBase,
 };
+use rustc_hash::FxBuildHasher;
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::fmt;
 use std::hash::BuildHasher;
+use std::hash::BuildHasherDefault;
 use std::hash::Hash;
+use std::hash::Hasher;
 use std::marker::PhantomData;
 use std::num::NonZeroU32;
 
+/// a snapshot of how full one level's table is, see `HashTables::stats`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LevelStats {
+    pub level: usize,
+    pub capacity: usize,
+    pub len: usize,
+    pub load_factor: f64,
+}
+
+/// why `HashTables::verify` failed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    /// a node whose child doesn't resolve in the level below
+    MissingChild {
+        /// the level of the node with the dangling child
+        level: usize,
+        key: BaseKey,
+        /// the child id that has no entry in level `level - 1`
+        missing_id: NonZeroU32,
+    },
+    /// level `level` has nodes, but level `level - 1` doesn't have explicit
+    /// ids enabled, so their children can't be resolved
+    ChildIdsUnavailable { level: usize },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerifyError::MissingChild {
+                level, missing_id, ..
+            } => write!(
+                f,
+                "node at level {} references id {} which is missing from level {}",
+                level,
+                missing_id,
+                level - 1
+            ),
+            VerifyError::ChildIdsUnavailable { level } => write!(
+                f,
+                "can't check the nodes at level {}: level {} doesn't have explicit ids",
+                level,
+                level - 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// why a step or `HashTables::get_or_insert_node` failed
+#[derive(Debug)]
+pub enum FailureReason {
+    TableFullOrSearchLimitHit,
+}
+
+impl<T> From<GetOrInsertFailureReason<T>> for FailureReason {
+    fn from(v: GetOrInsertFailureReason<T>) -> Self {
+        match v {
+            GetOrInsertFailureReason::TableFullOrSearchLimitHit { .. } => {
+                FailureReason::TableFullOrSearchLimitHit
+            }
+        }
+    }
+}
+
+/// `HashTables` is `Sync` (so `&HashTables` can be shared between the
+/// threads of the parallel step) when `Entry` and `BH` are `Sync`, and `Send`
+/// when they are `Send`. `SyncTableEntry` is `Sync` only when its early and
+/// late values are both `Send + Sync`; `LocalTableEntry` is never `Sync`.
 pub struct HashTables<Entry: TableEntry, BH: BuildHasher> {
     hash_tables: Vec<BaseHashTable<Entry, BH>>,
 }
 
+impl<Entry: TableEntry, BH: BuildHasher> HashTables<Entry, BH> {
+    /// creates one table per level, `capacities[level]` is the capacity for `level`
+    pub fn with_capacities_and_hasher(
+        capacities: impl IntoIterator<Item = usize>,
+        hasher: BH,
+    ) -> Self
+    where
+        BH: Clone,
+    {
+        Self {
+            hash_tables: capacities
+                .into_iter()
+                .map(|capacity| BaseHashTable::with_hasher(capacity, hasher.clone()))
+                .collect(),
+        }
+    }
+    pub fn with_capacities(capacities: impl IntoIterator<Item = usize>) -> Self
+    where
+        BH: Clone + Default,
+    {
+        Self::with_capacities_and_hasher(capacities, BH::default())
+    }
+    /// the number of levels there are tables for
+    pub fn level_count(&self) -> usize {
+        self.hash_tables.len()
+    }
+    /// the untyped table for level `L`, for reconfiguring it
+    pub fn get_mut<L: Level>(&mut self) -> &mut BaseHashTable<Entry, BH> {
+        &mut self.hash_tables[L::LEVEL]
+    }
+    /// iterates over the entries of level `L`'s table, with typed keys
+    pub fn iter_level<L: Level>(&self) -> impl Iterator<Item = (Key<L>, &Entry::Values)> {
+        self.hash_tables[L::LEVEL]
+            .iter()
+            .map(|(key, values)| (key.into(), values))
+    }
+    /// per-level fill counts, indexed by level. Cheap: only reads each table's
+    /// entry counter, but since other threads may be inserting, the levels
+    /// aren't a consistent snapshot of each other.
+    pub fn stats(&self) -> Vec<LevelStats> {
+        self.hash_tables
+            .iter()
+            .enumerate()
+            .map(|(level, hash_table)| {
+                // read `len` once so `load_factor` agrees with it even while
+                // other threads are inserting
+                let capacity = hash_table.capacity();
+                let len = hash_table.len();
+                LevelStats {
+                    level,
+                    capacity,
+                    len,
+                    load_factor: len as f64 / capacity as f64,
+                }
+            })
+            .collect()
+    }
+    /// checks that every child id of every non-leaf node names an entry in
+    /// the level below, returning the first problem found. Children can only
+    /// be resolved through explicit ids, so a non-empty level whose child
+    /// level doesn't have explicit ids enabled is an error rather than
+    /// passing unchecked. Nodes only reference the level below, so there
+    /// can't be cycles.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        for (level, levels) in self.hash_tables.windows(2).enumerate() {
+            let (child_table, table) = (&levels[0], &levels[1]);
+            if table.is_empty() {
+                continue;
+            }
+            if !child_table.has_explicit_ids() {
+                return Err(VerifyError::ChildIdsUnavailable { level: level + 1 });
+            }
+            let child_ids: HashSet<NonZeroU32, BuildIdHasher> =
+                child_table.explicit_ids().collect();
+            for (key, _) in table.iter() {
+                for &missing_id in key.0.iter().flatten().flatten() {
+                    if !child_ids.contains(&missing_id) {
+                        return Err(VerifyError::MissingChild {
+                            level: level + 1,
+                            key,
+                            missing_id,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    /// forgets all memoized step results (the late values) while keeping every
+    /// node and its early value, for when the rule changes
+    pub fn clear_step_cache(&mut self) {
+        for hash_table in &self.hash_tables {
+            for (_, values) in hash_table.iter() {
+                TableEntryValuesBase::set_late_value(values, None);
+            }
+        }
+    }
+}
+
+/// the number of levels created by `HashTables::default()`: levels `0..=20`
+pub const DEFAULT_LEVEL_COUNT: usize = 21;
+
+/// the capacity `HashTables::default()` uses for `level`.
+///
+/// There are many more distinct small nodes than large ones, so the leaf
+/// table gets `2^20` entries and each level above gets half of the level below
+/// it, bottoming out at `2^10` entries for level 10 and up. That is about
+/// 2.1 million entries in total, which is roughly 80MiB when each entry is 40
+/// bytes (the size of a `SyncTableEntry` with a zero-sized early value).
+pub fn default_level_capacity(level: usize) -> usize {
+    1 << (20 - level.min(10))
+}
+
+/// how table capacities vary by level, see `HashTables::with_capacity_curve`.
+/// Tables round their capacities up to a power of two.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CapacityCurve {
+    /// every level gets the same capacity
+    Uniform(usize),
+    /// level `n` gets `base * ratio^n`, rounded, and at least 1; a `ratio`
+    /// below 1 gives a big leaf table shrinking upward
+    Geometric { base: usize, ratio: f64 },
+    /// `capacities[level]` is the capacity for `level`
+    Explicit(Vec<usize>),
+}
+
+impl CapacityCurve {
+    /// the capacity for each of `level_count` levels; panics if this is
+    /// `Explicit` with a different number of levels
+    pub fn capacities(&self, level_count: usize) -> Vec<usize> {
+        match self {
+            &CapacityCurve::Uniform(capacity) => vec![capacity; level_count],
+            &CapacityCurve::Geometric { base, ratio } => (0..level_count)
+                .map(|level| {
+                    // `as` saturates, so huge capacities fail in the table constructor
+                    let capacity = (base as f64 * ratio.powi(level as i32)).round() as usize;
+                    capacity.max(1)
+                })
+                .collect(),
+            CapacityCurve::Explicit(capacities) => {
+                assert_eq!(
+                    capacities.len(),
+                    level_count,
+                    "explicit capacities must have one capacity per level"
+                );
+                capacities.clone()
+            }
+        }
+    }
+}
+
+impl<Entry: TableEntry, BH: BuildHasher + Clone + Default> HashTables<Entry, BH> {
+    pub fn with_capacity_curve(level_count: usize, curve: &CapacityCurve) -> Self {
+        Self::with_capacities(curve.capacities(level_count))
+    }
+}
+
+impl<Entry: TableEntry> Default for HashTables<Entry, FxBuildHasher> {
+    fn default() -> Self {
+        Self::with_capacities((0..DEFAULT_LEVEL_COUNT).map(default_level_capacity))
+    }
+}
+
 impl<Entry: TableEntry, BH: BuildHasher> HashTables<Entry, BH>
 where
     Entry::Values: TableEntryValuesBase<LateValue = NonZeroU32>,
@@ -28,6 +267,87 @@ where
     > {
         &self.hash_tables[L::LEVEL]
     }
+    /// the key (the children) of the node `id` names, for debugging ids from
+    /// crash dumps and the like. Like a `Key<L>`'s children, `id` names a node
+    /// in the table for `L::ParentLevel`, which must have explicit ids
+    /// enabled, otherwise this panics. Explicit ids move with their entries,
+    /// so this works after the table grows or shrinks, but it scans the whole
+    /// table, so it's slow.
+    ///
+    /// Only available in debug builds or with the `debug-ids` feature.
+    #[cfg(any(debug_assertions, feature = "debug-ids"))]
+    pub fn key_for_id<L: NonLeafLevel>(&self, id: Id<L>) -> Option<Key<L::ParentLevel>> {
+        self.hash_tables[L::ParentLevel::LEVEL]
+            .key_for_id(id.into())
+            .map(Key::from)
+    }
+    /// drops every node not reachable from `root`, returning how many were
+    /// dropped. `root` names a node in the table for `L::ParentLevel` (the
+    /// same way a `Key<L>`'s children do), so every table above that one is
+    /// emptied. Memoized step results that point at dropped nodes are cleared.
+    ///
+    /// Reachability is followed through explicit ids, so panics unless the
+    /// tables from the root's level down all have explicit ids enabled.
+    pub fn retain_reachable_from<L: NonLeafLevel>(&mut self, root: Id<L>) -> usize {
+        let root_level = L::ParentLevel::LEVEL;
+        let mut reachable_ids: Vec<HashSet<NonZeroU32, BuildIdHasher>> =
+            vec![HashSet::default(); root_level + 1];
+        let mut reachable_keys: Vec<HashSet<BaseKey>> = vec![HashSet::new(); root_level + 1];
+        reachable_ids[root_level].insert(root.into());
+        for level in (0..=root_level).rev() {
+            let (ids_below, ids) = reachable_ids.split_at_mut(level);
+            for (id, key, _) in self.hash_tables[level].iter_with_ids() {
+                if !ids[0].contains(&id) {
+                    continue;
+                }
+                reachable_keys[level].insert(key);
+                // leaf keys' children are cell states, not nodes
+                if let Some(ids_below) = ids_below.last_mut() {
+                    ids_below.extend(key.0.iter().flatten().flatten());
+                }
+            }
+        }
+        let mut dropped = 0;
+        for (level, hash_table) in self.hash_tables.iter_mut().enumerate() {
+            match reachable_keys.get(level) {
+                Some(keys) => dropped += hash_table.retain(|key, _| keys.contains(&key)),
+                None => {
+                    dropped += hash_table.len();
+                    hash_table.clear();
+                }
+            }
+        }
+        for level in 1..=root_level {
+            for (_, values) in self.hash_tables[level].iter() {
+                if let Some(late_value) = TableEntryValuesBase::late_value(values) {
+                    if !reachable_ids[level - 1].contains(&late_value) {
+                        TableEntryValuesBase::set_late_value(values, None);
+                    }
+                }
+            }
+        }
+        dropped
+    }
+}
+
+impl<Entry: TableEntry, BH: BuildHasher> HashTables<Entry, BH>
+where
+    Entry::Values: TableEntryValuesBase<LateValue = NonZeroU32>,
+    <Entry::Values as TableEntryValuesBase>::EarlyValue: Default,
+{
+    /// interns the node with `children`, with a default early value if it's
+    /// new, and returns its id. The node is stored in the table for
+    /// `L::ParentLevel`, which must have explicit ids enabled.
+    pub fn get_or_insert_node<L: NonLeafLevel>(
+        &self,
+        children: [[[Id<L::ParentLevel>; 2]; 2]; 2],
+    ) -> Result<Id<L>, FailureReason> {
+        let id = self.get::<L::ParentLevel>().intern(
+            Key::from_children(children),
+            TableEntryValues::new(Default::default(), None),
+        )?;
+        Ok(Id::from(NonZeroU32::from(id)))
+    }
 }
 
 pub trait Level: 'static + Copy + Eq + Hash + fmt::Debug {
@@ -89,9 +409,56 @@ impl<L: Level> From<Id<L>> for NonZeroU32 {
     }
 }
 
+/// a `Hasher` for `Id`s (and bare `NonZeroU32` ids) as keys of side tables,
+/// such as `HashMap<Id<L>, _, BuildIdHasher>`.
+///
+/// Hashing an id writes just its `u32`, which this multiplies by a 64-bit
+/// odd constant instead of running a general-purpose hash over it. Ids are
+/// handed out sequentially, and multiplying keeps sequential ids distinct in
+/// the low bits while also filling the high bits, which `std`'s `HashMap`
+/// uses to tag slots. The hash of an id is stable across runs and versions.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct IdHasher(u64);
+
+impl Hasher for IdHasher {
+    fn finish(&self) -> u64 {
+        self.0.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+    fn write_u32(&mut self, v: u32) {
+        self.0 = self.0.rotate_left(32) ^ u64::from(v);
+    }
+    /// only reached when hashing something other than an id; slow but correct
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(byte);
+        }
+    }
+}
+
+pub type BuildIdHasher = BuildHasherDefault<IdHasher>;
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub struct Key<L: Level>(pub [[[Id<L>; 2]; 2]; 2]);
 
+/// children are indexed as `[x][y][z]`, where index 0 is the half of the node
+/// with the smaller world coordinate along that axis and 1 is the larger half.
+impl<L: Level> Key<L> {
+    pub fn from_children(children: [[[Id<L>; 2]; 2]; 2]) -> Self {
+        Key(children)
+    }
+    pub fn children(&self) -> [[[Id<L>; 2]; 2]; 2] {
+        self.0
+    }
+    /// panics if any of `x`, `y`, or `z` is not 0 or 1
+    pub fn child(&self, x: usize, y: usize, z: usize) -> Id<L> {
+        self.0[x][y][z]
+    }
+    /// panics if any of `x`, `y`, or `z` is not 0 or 1
+    pub fn child_mut(&mut self, x: usize, y: usize, z: usize) -> &mut Id<L> {
+        &mut self.0[x][y][z]
+    }
+}
+
 impl<L: Level> From<Key<L>> for BaseKey {
     fn from(v: Key<L>) -> BaseKey {
         let [[[v000, v001], [v010, v011]], [[v100, v101], [v110, v111]]] = v.0;
@@ -112,12 +479,53 @@ impl<L: Level> From<BaseKey> for Key<L> {
     }
 }
 
+/// an error from converting raw ids, such as ones read from a file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// the id at `position` (indexed `[x, y, z]`) is zero, which is never a valid id
+    ZeroId { position: [usize; 3] },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::ZeroId {
+                position: [x, y, z],
+            } => {
+                write!(f, "id at position [{}, {}, {}] is zero", x, y, z)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl<L: Level> TryFrom<[[[u32; 2]; 2]; 2]> for Key<L> {
+    type Error = ParseError;
+    fn try_from(ids: [[[u32; 2]; 2]; 2]) -> Result<Key<L>, ParseError> {
+        let mut children = [[[Id::from(NonZeroU32::new(1).unwrap()); 2]; 2]; 2];
+        for (x, plane) in children.iter_mut().enumerate() {
+            for (y, row) in plane.iter_mut().enumerate() {
+                for (z, child) in row.iter_mut().enumerate() {
+                    let id = NonZeroU32::new(ids[x][y][z]).ok_or(ParseError::ZeroId {
+                        position: [x, y, z],
+                    })?;
+                    *child = Id::from(id);
+                }
+            }
+        }
+        Ok(Key(children))
+    }
+}
+
 pub trait TableEntryValues<L: Level> {
     type LateValue: Copy + 'static;
     type EarlyValue: Sized + 'static;
     fn new(early_value: Self::EarlyValue, late_value: Option<Self::LateValue>) -> Self;
     fn early_value(&self) -> &Self::EarlyValue;
     fn late_value(&self) -> Option<Self::LateValue>;
+    /// see `hashtable_base::TableEntryValues::peek_late_value` for when this is sound
+    fn peek_late_value(&self) -> Option<Self::LateValue>;
     fn set_late_value(&self, late_value: Option<Self::LateValue>);
     fn into(self) -> (Self::EarlyValue, Option<Self::LateValue>);
 }
@@ -134,6 +542,9 @@ impl<L: Level, Entry: TableEntryValuesBase<LateValue = NonZeroU32>> TableEntryVa
     fn late_value(&self) -> Option<Self::LateValue> {
         TableEntryValuesBase::late_value(self).map(Into::into)
     }
+    fn peek_late_value(&self) -> Option<Self::LateValue> {
+        TableEntryValuesBase::peek_late_value(self).map(Into::into)
+    }
     fn set_late_value(&self, late_value: Option<Self::LateValue>) {
         TableEntryValuesBase::set_late_value(self, late_value.map(Into::into));
     }
@@ -148,8 +559,16 @@ pub trait HashTable<L: Level> {
     type EarlyValue: Sized + 'static;
     type Values: TableEntryValues<L, EarlyValue = Self::EarlyValue, LateValue = Self::LateValue>;
     fn capacity(&self) -> usize;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool;
+    /// `len() / capacity()`
+    fn load_factor(&self) -> f64;
     fn insert_search_limit(&self) -> usize;
-    fn find(&self, key: Key<L>) -> Option<&Self::Values>;
+    fn find(&self, key: Key<L>) -> Option<Ref<Self::Values>>;
+    fn contains_key(&self, key: Key<L>) -> bool;
+    /// looks up both the early and late values in one lookup
+    #[allow(clippy::type_complexity)]
+    fn find_full(&self, key: Key<L>) -> Option<(Ref<Self::EarlyValue>, Option<Self::LateValue>)>;
     fn insert(
         &self,
         key: Key<L>,
@@ -160,9 +579,18 @@ pub trait HashTable<L: Level> {
         key: Key<L>,
         value: Self::Values,
     ) -> Result<GetOrInsertSuccess<Self::Values>, GetOrInsertFailureReason<Self::Values>>;
+    /// like `get_or_insert`, but returns the explicit id of `key`'s entry,
+    /// which is how the level above refers to it; panics if explicit ids are
+    /// not enabled
+    fn intern(
+        &self,
+        key: Key<L>,
+        value: Self::Values,
+    ) -> Result<Id<NonLeaf<L>>, GetOrInsertFailureReason<Self::Values>>;
 }
 
-impl<L: Level, Entry: TableEntry, BH: BuildHasher> HashTable<L> for BaseHashTable<Entry, BH>
+impl<L: Level, Entry: TableEntry, BH: BuildHasher, P: ProbeSequence> HashTable<L>
+    for BaseHashTable<Entry, BH, P>
 where
     Entry::Values: TableEntryValues<L>,
 {
@@ -172,12 +600,32 @@ where
     fn capacity(&self) -> usize {
         BaseHashTable::capacity(self)
     }
+    fn len(&self) -> usize {
+        BaseHashTable::len(self)
+    }
+    fn is_empty(&self) -> bool {
+        BaseHashTable::is_empty(self)
+    }
+    fn load_factor(&self) -> f64 {
+        BaseHashTable::load_factor(self)
+    }
     fn insert_search_limit(&self) -> usize {
         BaseHashTable::insert_search_limit(self)
     }
-    fn find(&self, key: Key<L>) -> Option<&Self::Values> {
+    fn find(&self, key: Key<L>) -> Option<Ref<Self::Values>> {
         BaseHashTable::find(self, key.into())
     }
+    fn contains_key(&self, key: Key<L>) -> bool {
+        BaseHashTable::contains_key(self, key.into())
+    }
+    fn find_full(&self, key: Key<L>) -> Option<(Ref<Self::EarlyValue>, Option<Self::LateValue>)> {
+        let values = BaseHashTable::find(self, key.into())?;
+        let late_value = TableEntryValues::<L>::late_value(&*values);
+        Some((
+            Ref::map(values, TableEntryValues::<L>::early_value),
+            late_value,
+        ))
+    }
     fn insert(
         &self,
         key: Key<L>,
@@ -192,4 +640,656 @@ where
     ) -> Result<GetOrInsertSuccess<Self::Values>, GetOrInsertFailureReason<Self::Values>> {
         BaseHashTable::get_or_insert(self, key.into(), value)
     }
+    fn intern(
+        &self,
+        key: Key<L>,
+        value: Self::Values,
+    ) -> Result<Id<NonLeaf<L>>, GetOrInsertFailureReason<Self::Values>> {
+        BaseHashTable::intern(self, key.into(), value).map(Id::from)
+    }
+}
+
+impl<L: Level, Values: TableEntryValues<L>, BH: BuildHasher> HashTable<L>
+    for RobinHoodHashTable<Values, BH>
+{
+    type EarlyValue = Values::EarlyValue;
+    type LateValue = Values::LateValue;
+    type Values = Values;
+    fn capacity(&self) -> usize {
+        RobinHoodHashTable::capacity(self)
+    }
+    fn len(&self) -> usize {
+        RobinHoodHashTable::len(self)
+    }
+    fn is_empty(&self) -> bool {
+        RobinHoodHashTable::is_empty(self)
+    }
+    fn load_factor(&self) -> f64 {
+        RobinHoodHashTable::load_factor(self)
+    }
+    /// inserts search until they find an empty slot
+    fn insert_search_limit(&self) -> usize {
+        RobinHoodHashTable::capacity(self)
+    }
+    fn find(&self, key: Key<L>) -> Option<Ref<Self::Values>> {
+        RobinHoodHashTable::find(self, key.into()).map(Ref::new)
+    }
+    fn contains_key(&self, key: Key<L>) -> bool {
+        RobinHoodHashTable::contains_key(self, key.into())
+    }
+    fn find_full(&self, key: Key<L>) -> Option<(Ref<Self::EarlyValue>, Option<Self::LateValue>)> {
+        let values = RobinHoodHashTable::find(self, key.into())?;
+        Some((
+            Ref::new(TableEntryValues::<L>::early_value(values)),
+            TableEntryValues::<L>::late_value(values),
+        ))
+    }
+    fn insert(
+        &self,
+        key: Key<L>,
+        value: Self::Values,
+    ) -> Result<&Self::Values, InsertFailureReason<Self::Values>> {
+        RobinHoodHashTable::insert(self, key.into(), value)
+    }
+    fn get_or_insert(
+        &self,
+        key: Key<L>,
+        value: Self::Values,
+    ) -> Result<GetOrInsertSuccess<Self::Values>, GetOrInsertFailureReason<Self::Values>> {
+        RobinHoodHashTable::get_or_insert(self, key.into(), value)
+    }
+    fn intern(
+        &self,
+        key: Key<L>,
+        value: Self::Values,
+    ) -> Result<Id<NonLeaf<L>>, GetOrInsertFailureReason<Self::Values>> {
+        RobinHoodHashTable::intern(self, key.into(), value).map(Id::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashtable_base::LocalTableEntry;
+    use crate::hashtable_base::SyncTableEntry;
+
+    type Level4 = NonLeaf<Level3>;
+
+    #[test]
+    fn test_key_children() {
+        let id = |v| Id::<Level4>::from(NonZeroU32::new(v).unwrap());
+        let mut key = Key([
+            [[id(1), id(2)], [id(3), id(4)]],
+            [[id(5), id(6)], [id(7), id(8)]],
+        ]);
+        assert_eq!(Key::from_children(key.children()), key);
+        assert_eq!(key.child(0, 0, 0), id(1));
+        assert_eq!(key.child(0, 0, 1), id(2));
+        assert_eq!(key.child(0, 1, 0), id(3));
+        assert_eq!(key.child(1, 0, 0), id(5));
+        assert_eq!(key.child(1, 1, 1), id(8));
+        *key.child_mut(1, 0, 1) = id(9);
+        assert_eq!(key.child(1, 0, 1), id(9));
+        assert_eq!(key.children()[1][0][1], id(9));
+    }
+
+    /// allocates the full default of about 80MiB, but the tables are zeroed
+    /// allocations, so only the pages the test touches are backed by memory
+    #[test]
+    fn test_default_hash_tables() {
+        let hash_tables = HashTables::<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>::default();
+        assert_eq!(hash_tables.level_count(), DEFAULT_LEVEL_COUNT);
+        assert_eq!(hash_tables.get::<Leaf>().capacity(), 1 << 20);
+        assert_eq!(hash_tables.get::<Level1>().capacity(), 1 << 19);
+        assert_eq!(hash_tables.get::<Level4>().capacity(), 1 << 16);
+        assert_eq!(default_level_capacity(10), 1 << 10);
+        assert_eq!(default_level_capacity(DEFAULT_LEVEL_COUNT - 1), 1 << 10);
+        let id = |v| Id::<Level2>::from(NonZeroU32::new(v).unwrap());
+        let key = Key::<Level2>([
+            [[id(1), id(2)], [id(3), id(4)]],
+            [[id(5), id(6)], [id(7), id(8)]],
+        ]);
+        let table = hash_tables.get::<Level2>();
+        assert!(table.find(key).is_none());
+        assert!(table.is_empty());
+        assert!(table.insert(key, TableEntryValues::new((), None)).is_ok());
+        assert!(table.find(key).is_some());
+        assert!(!table.is_empty());
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.load_factor(), 1.0 / (1 << 18) as f64);
+    }
+
+    /// stepping isn't implemented yet, so this only checks the default tables
+    /// have room to build a small pattern all the way up to the top level
+    #[test]
+    fn test_default_hash_tables_pattern() {
+        type Entry = SyncTableEntry<(), NonZeroU32>;
+        let mut hash_tables = HashTables::<Entry, FxBuildHasher>::default();
+        for hash_table in &mut hash_tables.hash_tables {
+            hash_table.enable_explicit_ids();
+        }
+        let intern = |level: usize, key: BaseKey| {
+            let values = <<Entry as TableEntry>::Values as TableEntryValuesBase>::new((), None);
+            match hash_tables.hash_tables[level].intern(key, values) {
+                Ok(id) => id,
+                Err(_) => panic!("default table for level {} is full", level),
+            }
+        };
+        let dead = NonZeroU32::new(1).unwrap();
+        let alive = NonZeroU32::new(2).unwrap();
+        let mut empty = intern(0, BaseKey([[[dead; 2]; 2]; 2]));
+        let mut pattern = intern(
+            0,
+            BaseKey([[[alive, dead], [dead, dead]], [[dead, dead], [dead, alive]]]),
+        );
+        // each level's pattern node has the level below's in its first
+        // octant and is empty everywhere else
+        for level in 1..DEFAULT_LEVEL_COUNT {
+            let mut key = BaseKey([[[empty; 2]; 2]; 2]);
+            key.0[0][0][0] = pattern;
+            pattern = intern(level, key);
+            empty = intern(level, BaseKey([[[empty; 2]; 2]; 2]));
+        }
+        assert!(hash_tables.stats().iter().all(|stats| stats.len == 2));
+        assert_eq!(hash_tables.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_find_full() {
+        let hash_tables =
+            HashTables::<SyncTableEntry<u32, NonZeroU32>, FxBuildHasher>::with_capacities(vec![
+                16;
+                4
+            ]);
+        let table = hash_tables.get::<Level1>();
+        let id = |v| Id::<Level1>::from(NonZeroU32::new(v).unwrap());
+        let key1 = Key([[[id(1); 2]; 2]; 2]);
+        let key2 = Key([[[id(2); 2]; 2]; 2]);
+        let key3 = Key([[[id(3); 2]; 2]; 2]);
+        assert!(table.insert(key1, TableEntryValues::new(10, None)).is_ok());
+        assert!(table
+            .insert(key2, TableEntryValues::new(20, Some(id(5))))
+            .is_ok());
+        for &key in &[key1, key2, key3] {
+            let expected = table
+                .find(key)
+                .map(|values| (*values.early_value(), values.late_value()));
+            let found = table
+                .find_full(key)
+                .map(|(early_value, late_value)| (*early_value, late_value));
+            assert_eq!(found, expected);
+        }
+        assert_eq!(table.find_full(key2).unwrap().1, Some(id(5)));
+        assert!(table.find_full(key3).is_none());
+        assert!(table.contains_key(key2));
+        assert!(!table.contains_key(key3));
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut hash_tables =
+            HashTables::<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>::with_capacities(vec![
+                16;
+                4
+            ]);
+        assert_eq!(hash_tables.get::<Level1>().insert_search_limit(), 32);
+        hash_tables.get_mut::<Level1>().set_insert_search_limit(5);
+        assert_eq!(hash_tables.get::<Level1>().insert_search_limit(), 5);
+        assert_eq!(hash_tables.get::<Level2>().insert_search_limit(), 32);
+    }
+
+    #[test]
+    fn test_clear_step_cache() {
+        let mut hash_tables =
+            HashTables::<SyncTableEntry<u32, NonZeroU32>, FxBuildHasher>::with_capacities(vec![
+                16;
+                4
+            ]);
+        let id1 = |v| Id::<Level1>::from(NonZeroU32::new(v).unwrap());
+        let id2 = |v| Id::<Level2>::from(NonZeroU32::new(v).unwrap());
+        let keys1: Vec<_> = (1..=5).map(|v| Key([[[id1(v); 2]; 2]; 2])).collect();
+        let keys2: Vec<_> = (1..=5).map(|v| Key([[[id2(v); 2]; 2]; 2])).collect();
+        for (i, (&key1, &key2)) in keys1.iter().zip(&keys2).enumerate() {
+            let i = i as u32 + 1;
+            assert!(hash_tables
+                .get::<Level1>()
+                .insert(key1, TableEntryValues::new(i, Some(id1(i))))
+                .is_ok());
+            assert!(hash_tables
+                .get::<Level2>()
+                .insert(key2, TableEntryValues::new(i * 10, Some(id2(i))))
+                .is_ok());
+        }
+        hash_tables.clear_step_cache();
+        for (i, (&key1, &key2)) in keys1.iter().zip(&keys2).enumerate() {
+            let i = i as u32 + 1;
+            assert_eq!(
+                hash_tables
+                    .get::<Level1>()
+                    .find_full(key1)
+                    .map(|(early_value, late_value)| (*early_value, late_value)),
+                Some((i, None))
+            );
+            assert_eq!(
+                hash_tables
+                    .get::<Level2>()
+                    .find_full(key2)
+                    .map(|(early_value, late_value)| (*early_value, late_value)),
+                Some((i * 10, None))
+            );
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let hash_tables =
+            HashTables::<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>::with_capacities(vec![
+                64, 32, 16,
+            ]);
+        let leaf = |v| Id::<Leaf>::from(NonZeroU32::new(v).unwrap());
+        let id1 = |v| Id::<Level1>::from(NonZeroU32::new(v).unwrap());
+        // every combination of two of three ids at level 0, and at level 1
+        // the unordered pairs, so some inserts are duplicates
+        let mut expected_level0 = std::collections::HashSet::new();
+        let mut expected_level1 = std::collections::HashSet::new();
+        for a in 1..=3 {
+            for b in 1..=3 {
+                let key = Key([[[leaf(a), leaf(b)]; 2]; 2]);
+                let _ = hash_tables
+                    .get::<Leaf>()
+                    .get_or_insert(key, TableEntryValues::new((), None));
+                expected_level0.insert(key);
+                let key = Key([[[id1(a.min(b)), id1(a.max(b))]; 2]; 2]);
+                let _ = hash_tables
+                    .get::<Level1>()
+                    .get_or_insert(key, TableEntryValues::new((), None));
+                expected_level1.insert(key);
+            }
+        }
+        let stats = hash_tables.stats();
+        assert_eq!(
+            stats,
+            vec![
+                LevelStats {
+                    level: 0,
+                    capacity: 64,
+                    len: expected_level0.len(),
+                    load_factor: expected_level0.len() as f64 / 64.0,
+                },
+                LevelStats {
+                    level: 1,
+                    capacity: 32,
+                    len: expected_level1.len(),
+                    load_factor: expected_level1.len() as f64 / 32.0,
+                },
+                LevelStats {
+                    level: 2,
+                    capacity: 16,
+                    len: 0,
+                    load_factor: 0.0,
+                },
+            ]
+        );
+        assert_eq!(stats[0].len, 9);
+        assert_eq!(stats[1].len, 6);
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<HashTables<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>>();
+        assert_send_sync::<HashTables<SyncTableEntry<u32, NonZeroU32>, FxBuildHasher>>();
+        assert_send_sync::<
+            HashTables<
+                SyncTableEntry<Vec<u8>, NonZeroU32>,
+                std::collections::hash_map::RandomState,
+            >,
+        >();
+        assert_send_sync::<BaseHashTable<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>>();
+    }
+
+    #[test]
+    fn test_iter_level() {
+        let hash_tables =
+            HashTables::<SyncTableEntry<u32, NonZeroU32>, FxBuildHasher>::with_capacities(vec![
+                16;
+                4
+            ]);
+        let id = |v| Id::<Level2>::from(NonZeroU32::new(v).unwrap());
+        let keys: Vec<Key<Level2>> = (1..=5)
+            .map(|v| {
+                Key([
+                    [[id(v), id(v + 1)], [id(v + 2), id(v + 3)]],
+                    [[id(v + 4), id(v + 5)], [id(v + 6), id(v + 7)]],
+                ])
+            })
+            .collect();
+        for (i, &key) in keys.iter().enumerate() {
+            assert!(hash_tables
+                .get::<Level2>()
+                .insert(key, TableEntryValues::new(i as u32, None))
+                .is_ok());
+        }
+        let mut entries: Vec<(Key<Level2>, u32)> = hash_tables
+            .iter_level::<Level2>()
+            .map(|(key, values)| (key, *TableEntryValuesBase::early_value(values)))
+            .collect();
+        entries.sort_by_key(|&(_, i)| i);
+        let expected: Vec<_> = keys.iter().copied().zip(0..).collect();
+        assert_eq!(entries, expected);
+        assert_eq!(hash_tables.iter_level::<Level1>().count(), 0);
+    }
+
+    #[test]
+    fn test_verify() {
+        type Entry = SyncTableEntry<(), NonZeroU32>;
+        let mut hash_tables = HashTables::<Entry, FxBuildHasher>::with_capacities(vec![16; 3]);
+        hash_tables.get_mut::<Leaf>().enable_explicit_ids();
+        let base_key = |v| BaseKey([[[NonZeroU32::new(v).unwrap(); 2]; 2]; 2]);
+        let values = || <<Entry as TableEntry>::Values as TableEntryValuesBase>::new((), None);
+        let intern_leaf = |hash_tables: &HashTables<Entry, FxBuildHasher>, v| {
+            let leaf_table = &hash_tables.hash_tables[Leaf::LEVEL];
+            Id::<Level1>::from(leaf_table.intern(base_key(v), values()).ok().unwrap())
+        };
+        let dead = intern_leaf(&hash_tables, 1);
+        let alive = intern_leaf(&hash_tables, 2);
+        let key = Key([[[dead, alive], [dead, dead]], [[dead, dead], [dead, dead]]]);
+        assert!(hash_tables
+            .get::<Level1>()
+            .insert(key, TableEntryValues::new((), None))
+            .is_ok());
+        // level 2 is empty, so level 1 not having explicit ids is fine
+        assert_eq!(hash_tables.verify(), Ok(()));
+
+        // remove `alive` by rebuilding the leaf table without it; ids aren't
+        // reused, so `dead` gets a new id and its old one dangles too
+        hash_tables.get_mut::<Leaf>().clear();
+        intern_leaf(&hash_tables, 1);
+        match hash_tables.verify() {
+            Err(VerifyError::MissingChild {
+                level,
+                key: error_key,
+                missing_id,
+            }) => {
+                assert_eq!(level, 1);
+                assert_eq!(Key::<Level1>::from(error_key), key);
+                assert!(missing_id == dead.into() || missing_id == alive.into());
+            }
+            result => panic!("expected a missing child, got {:?}", result),
+        }
+
+        // level 2's children can't be resolved without explicit ids in level 1
+        let unresolved = Id::from(NonZeroU32::new(100).unwrap());
+        assert!(hash_tables
+            .get::<Level2>()
+            .insert(
+                Key([[[unresolved; 2]; 2]; 2]),
+                TableEntryValues::new((), None)
+            )
+            .is_ok());
+        hash_tables.get_mut::<Level1>().clear();
+        assert_eq!(
+            hash_tables.verify(),
+            Err(VerifyError::ChildIdsUnavailable { level: 2 })
+        );
+    }
+
+    #[test]
+    fn test_id_hasher() {
+        let id = |v| Id::<Level1>::from(NonZeroU32::new(v).unwrap());
+        let hash = |v| {
+            let mut hasher = IdHasher::default();
+            id(v).hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(5), hash(5));
+        let mut buckets: Vec<u64> = (1..=64).map(|v| hash(v) & 63).collect();
+        buckets.sort_unstable();
+        assert_eq!(buckets, (0..64).collect::<Vec<_>>());
+        let mut tags: Vec<u64> = (1..=64).map(|v| hash(v) >> 57).collect();
+        tags.sort_unstable();
+        tags.dedup();
+        assert!(tags.len() > 32, "high bits should vary: {:?}", tags);
+        let map: std::collections::HashMap<Id<Level1>, u32, BuildIdHasher> =
+            (1..=1000).map(|v| (id(v), v)).collect();
+        assert!((1..=1000).all(|v| map[&id(v)] == v));
+    }
+
+    #[test]
+    fn test_retain_reachable_from() {
+        type Entry = SyncTableEntry<(), NonZeroU32>;
+        let mut hash_tables = HashTables::<Entry, FxBuildHasher>::with_capacities(vec![16; 4]);
+        for level in 0..3 {
+            hash_tables.hash_tables[level].enable_explicit_ids();
+        }
+        let intern = |level: usize, key: BaseKey| {
+            let values = <<Entry as TableEntry>::Values as TableEntryValuesBase>::new((), None);
+            hash_tables.hash_tables[level]
+                .intern(key, values)
+                .ok()
+                .unwrap()
+        };
+        let uniform = |id| BaseKey([[[id; 2]; 2]; 2]);
+        let pair = |a, b| BaseKey([[[a, b]; 2]; 2]);
+        let cell = |v| NonZeroU32::new(v).unwrap();
+        let leaf_a = intern(0, uniform(cell(1)));
+        let leaf_b = intern(0, pair(cell(1), cell(2)));
+        let leaf_c = intern(0, uniform(cell(2)));
+        let level1_kept = intern(1, pair(leaf_a, leaf_b));
+        let level1_dropped = intern(1, uniform(leaf_c));
+        let shared = intern(1, uniform(leaf_a));
+        let root = intern(2, pair(level1_kept, shared));
+        intern(2, pair(level1_dropped, shared));
+        hash_tables
+            .get::<Level3>()
+            .insert(
+                Key([[[Id::from(root); 2]; 2]; 2]),
+                TableEntryValues::new((), None),
+            )
+            .ok()
+            .unwrap();
+        // memoized results pointing at a kept node and at a dropped node
+        let set_late_value = |level: usize, key, late_value| {
+            TableEntryValuesBase::set_late_value(
+                &*hash_tables.hash_tables[level].find(key).unwrap(),
+                Some(late_value),
+            )
+        };
+        set_late_value(1, pair(leaf_a, leaf_b), leaf_a);
+        set_late_value(1, uniform(leaf_a), leaf_c);
+
+        let dropped = hash_tables.retain_reachable_from(Id::<Level3>::from(root));
+        assert_eq!(dropped, 4);
+        assert_eq!(
+            hash_tables
+                .stats()
+                .iter()
+                .map(|stats| stats.len)
+                .collect::<Vec<_>>(),
+            vec![2, 2, 1, 0]
+        );
+        assert_eq!(hash_tables.hash_tables[0].id_of(uniform(cell(2))), None);
+        assert_eq!(
+            hash_tables.hash_tables[1].id_of(pair(leaf_a, leaf_b)),
+            Some(level1_kept)
+        );
+        assert_eq!(
+            hash_tables.hash_tables[2].id_of(pair(level1_kept, shared)),
+            Some(root)
+        );
+        let late_value = |level: usize, key| {
+            TableEntryValuesBase::late_value(&*hash_tables.hash_tables[level].find(key).unwrap())
+        };
+        assert_eq!(late_value(1, pair(leaf_a, leaf_b)), Some(leaf_a));
+        assert_eq!(late_value(1, uniform(leaf_a)), None);
+        assert_eq!(hash_tables.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_capacity_curve() {
+        let curve = CapacityCurve::Geometric {
+            base: 1 << 12,
+            ratio: 0.5,
+        };
+        assert_eq!(
+            curve.capacities(15),
+            vec![4096, 2048, 1024, 512, 256, 128, 64, 32, 16, 8, 4, 2, 1, 1, 1]
+        );
+        assert_eq!(CapacityCurve::Uniform(7).capacities(3), vec![7, 7, 7]);
+        let hash_tables =
+            HashTables::<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>::with_capacity_curve(
+                4,
+                &CapacityCurve::Geometric {
+                    base: 1000,
+                    ratio: 0.25,
+                },
+            );
+        let capacities: Vec<usize> = hash_tables
+            .stats()
+            .iter()
+            .map(|stats| stats.capacity)
+            .collect();
+        assert_eq!(capacities, vec![1024, 256, 64, 16]);
+        let hash_tables =
+            HashTables::<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>::with_capacity_curve(
+                2,
+                &CapacityCurve::Explicit(vec![4, 32]),
+            );
+        assert_eq!(hash_tables.get::<Level1>().capacity(), 32);
+    }
+
+    #[test]
+    #[should_panic(expected = "explicit capacities must have one capacity per level")]
+    fn test_capacity_curve_explicit_wrong_length() {
+        CapacityCurve::Explicit(vec![1, 2]).capacities(3);
+    }
+
+    #[test]
+    fn test_key_try_from_u32s() {
+        let id = |v| Id::<Level2>::from(NonZeroU32::new(v).unwrap());
+        assert_eq!(
+            Key::<Level2>::try_from([[[1, 2], [3, 4]], [[5, 6], [7, 8]]]),
+            Ok(Key([
+                [[id(1), id(2)], [id(3), id(4)]],
+                [[id(5), id(6)], [id(7), id(8)]],
+            ]))
+        );
+        let error = Key::<Level2>::try_from([[[1, 2], [3, 4]], [[5, 0], [7, 0]]]).unwrap_err();
+        assert_eq!(
+            error,
+            ParseError::ZeroId {
+                position: [1, 0, 1]
+            }
+        );
+        assert_eq!(error.to_string(), "id at position [1, 0, 1] is zero");
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "debug-ids"))]
+    fn test_key_for_id() {
+        let mut hash_tables =
+            HashTables::<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>::with_capacities(vec![
+                4;
+                3
+            ]);
+        hash_tables.get_mut::<Level1>().enable_explicit_ids();
+        let id = |v| Id::<Level1>::from(NonZeroU32::new(v).unwrap());
+        let keys: Vec<Key<Level1>> = (1..=3).map(|v| Key([[[id(v), id(v + 1)]; 2]; 2])).collect();
+        let ids: Vec<Id<Level2>> = keys
+            .iter()
+            .map(|&key| {
+                hash_tables
+                    .get::<Level1>()
+                    .intern(key, TableEntryValues::new((), None))
+                    .ok()
+                    .unwrap()
+            })
+            .collect();
+        for (&key, &id) in keys.iter().zip(&ids) {
+            assert_eq!(hash_tables.key_for_id(id), Some(key));
+        }
+        hash_tables.get_mut::<Level1>().reserve(100);
+        for (&key, &id) in keys.iter().zip(&ids) {
+            assert_eq!(hash_tables.key_for_id(id), Some(key));
+        }
+        let unused = Id::<Level2>::from(NonZeroU32::new(100).unwrap());
+        assert_eq!(hash_tables.key_for_id(unused), None);
+    }
+
+    #[test]
+    fn test_get_or_insert_node() {
+        let mut hash_tables =
+            HashTables::<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>::with_capacities(vec![
+                16;
+                3
+            ]);
+        hash_tables.get_mut::<Leaf>().enable_explicit_ids();
+        hash_tables.get_mut::<Level1>().enable_explicit_ids();
+        let dead = Id::<Leaf>::from(NonZeroU32::new(1).unwrap());
+        let alive = Id::<Leaf>::from(NonZeroU32::new(2).unwrap());
+        let empty_leaf = hash_tables
+            .get_or_insert_node::<Level1>([[[dead; 2]; 2]; 2])
+            .unwrap();
+        let mut corner = [[[dead; 2]; 2]; 2];
+        corner[1][1][1] = alive;
+        let corner_leaf = hash_tables.get_or_insert_node::<Level1>(corner).unwrap();
+        assert_ne!(empty_leaf, corner_leaf);
+        let mut children = [[[empty_leaf; 2]; 2]; 2];
+        children[0][0][0] = corner_leaf;
+        let node = hash_tables.get_or_insert_node::<Level2>(children).unwrap();
+        assert_eq!(
+            hash_tables.get_or_insert_node::<Level2>(children).unwrap(),
+            node
+        );
+        assert_eq!(
+            hash_tables
+                .get_or_insert_node::<Level1>([[[dead; 2]; 2]; 2])
+                .unwrap(),
+            empty_leaf
+        );
+        let lens: Vec<usize> = hash_tables.stats().iter().map(|stats| stats.len).collect();
+        assert_eq!(lens, vec![2, 1, 0]);
+        assert!(hash_tables
+            .get::<Level1>()
+            .find(Key::from_children(children))
+            .is_some());
+    }
+
+    #[test]
+    fn test_robin_hood_hash_table_trait() {
+        type Values = <LocalTableEntry<u32, NonZeroU32> as TableEntry>::Values;
+        let table = RobinHoodHashTable::<Values, FxBuildHasher>::new(4);
+        let id = |v| Id::<Level1>::from(NonZeroU32::new(v).unwrap());
+        let keys: Vec<Key<Level1>> = (1..=5).map(|v| Key([[[id(v); 2]; 2]; 2])).collect();
+        let table: &dyn HashTable<
+            Level1,
+            EarlyValue = u32,
+            LateValue = Id<Level1>,
+            Values = Values,
+        > = &table;
+        let values = |early_value| <Values as TableEntryValues<Level1>>::new(early_value, None);
+        let mut ids = Vec::new();
+        for (i, &key) in keys[..4].iter().enumerate() {
+            ids.push(table.intern(key, values(i as u32)).ok().unwrap());
+        }
+        assert_eq!(table.len(), 4);
+        assert!((table.load_factor() - 1.0).abs() < 1e-9);
+        for (i, &key) in keys[..4].iter().enumerate() {
+            let (early_value, late_value) = table.find_full(key).unwrap();
+            assert_eq!((*early_value, late_value), (i as u32, None));
+            match table.insert(key, values(0)) {
+                Err(InsertFailureReason::AlreadyInTable { id, .. }) => {
+                    assert_eq!(id.map(Id::<Level2>::from), Some(ids[i]))
+                }
+                _ => panic!("expected key to already be in table"),
+            }
+            // ids are stable, so interning again returns the same id
+            assert_eq!(table.intern(key, values(0)).ok().unwrap(), ids[i]);
+        }
+        TableEntryValues::<Level1>::set_late_value(&*table.find(keys[0]).unwrap(), Some(id(7)));
+        assert_eq!(table.find_full(keys[0]).unwrap().1, Some(id(7)));
+        assert!(!table.contains_key(keys[4]));
+        assert!(table.get_or_insert(keys[4], values(4)).is_err());
+    }
 }
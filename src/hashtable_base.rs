@@ -1,15 +1,54 @@
+use std::alloc::alloc_zeroed;
+use std::alloc::handle_alloc_error;
+use std::alloc::Layout;
+use std::fmt;
+#[cfg(feature = "memmap2")]
+use std::fs::File;
 use std::hash::BuildHasher;
 use std::hash::Hash;
 use std::hash::Hasher;
+#[cfg(feature = "memmap2")]
+use std::io;
+use std::marker::PhantomData;
 use std::num::NonZeroU32;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::ptr;
+use std::sync::atomic::spin_loop_hint;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+/// ordered lexicographically over the ids in `[x][y][z]` order
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct Key(pub [[[NonZeroU32; 2]; 2]; 2]);
 
+/// counts one operation in `$table`'s `StatCounters`; a no-op without the
+/// `stats` feature
+macro_rules! count_stat {
+    ($table:expr, $counter:ident) => {
+        #[cfg(feature = "stats")]
+        $table
+            .stats
+            .$counter
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    };
+}
+
+mod cached;
 mod local;
+#[cfg(feature = "memmap2")]
+mod mmap;
+mod robin_hood;
 mod sync;
 
+pub use cached::CachedHashTable;
 pub use local::LocalTableEntry;
+pub use robin_hood::RobinHoodHashTable;
+pub use sync::backoff_count;
+pub use sync::AcquireReleaseOrdering;
+pub use sync::LateValueOrdering;
+pub use sync::RelaxedOrdering;
 pub use sync::SyncTableEntry;
 
 #[derive(Debug)]
@@ -36,25 +75,263 @@ pub trait TableEntryValues:
     fn new(early_value: Self::EarlyValue, late_value: Option<Self::LateValue>) -> Self;
     fn early_value(&self) -> &Self::EarlyValue;
     fn late_value(&self) -> Option<Self::LateValue>;
+    /// like `late_value`, but with no ordering guarantees on the sync backend.
+    ///
+    /// Only use this when the read already happens-after the `set_late_value`
+    /// it should observe through other synchronization, such as joining the
+    /// threads that stepped. Otherwise it can return a late value that refers
+    /// to a node whose insertion isn't visible to this thread yet.
+    fn peek_late_value(&self) -> Option<Self::LateValue> {
+        self.late_value()
+    }
     fn set_late_value(&self, late_value: Option<Self::LateValue>);
 }
 
+/// proof that an `Entry` made entirely of zero bytes is a valid empty entry
+pub struct ZeroedIsEmpty<Entry>(PhantomData<fn() -> Entry>);
+
+impl<Entry> ZeroedIsEmpty<Entry> {
+    /// # Safety
+    ///
+    /// An `Entry` made entirely of zero bytes must be valid and must be empty.
+    pub const unsafe fn new() -> Self {
+        ZeroedIsEmpty(PhantomData)
+    }
+}
+
 pub trait TableEntry {
     type Values: TableEntryValues;
     fn empty() -> Self;
     fn get(&self) -> Option<(Key, &Self::Values)>;
+    /// like `get`, but may skip synchronizing with other threads.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may be filling this entry, and every fill of it must
+    /// happen-before this call, such as by joining the threads that filled it.
+    unsafe fn get_unsynchronized(&self) -> Option<(Key, &Self::Values)> {
+        self.get()
+    }
     fn fill(
         &self,
         key: Key,
         value: Self::Values,
     ) -> Result<&Self::Values, AlreadyFull<Self::Values>>;
     fn take(&mut self) -> Option<(Key, Self::Values)>;
+    /// `key.0[0][0]` of a full entry, for rejecting mismatched keys without
+    /// reading the whole key. `None` means the caller has to use `get`.
+    ///
+    /// Only worth overriding when it's cheaper than `get`, since lookups call
+    /// `get` anyway once `peek_key00` doesn't reject the entry.
+    fn peek_key00(&self) -> Option<[NonZeroU32; 2]> {
+        None
+    }
+    /// `Some` if an entry made entirely of zero bytes is a valid empty entry
+    fn zeroed_is_empty() -> Option<ZeroedIsEmpty<Self>>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+/// allocates `len` empty entries, using a zeroed allocation instead of
+/// constructing each entry when `Entry::zeroed_is_empty()` allows
+fn try_new_empty_entries<Entry: TableEntry>(len: usize) -> Result<Box<[Entry]>, CapacityError> {
+    let layout =
+        Layout::array::<Entry>(len).map_err(|_| CapacityError::TooBig { capacity: len })?;
+    if Entry::zeroed_is_empty().is_some() && layout.size() != 0 {
+        unsafe {
+            let entries = alloc_zeroed(layout) as *mut Entry;
+            if entries.is_null() {
+                return Err(CapacityError::AllocFailed { capacity: len });
+            }
+            // safety: allocated with the layout `Box<[Entry]>` uses, and
+            // all-zero entries are valid and empty
+            let entries = Box::from_raw(ptr::slice_from_raw_parts_mut(entries, len));
+            debug_assert!(
+                entries[0].get().is_none(),
+                "zeroed entry isn't empty despite `zeroed_is_empty`"
+            );
+            return Ok(entries);
+        }
+    }
+    let mut entries = Vec::new();
+    entries
+        .try_reserve_exact(len)
+        .map_err(|_| CapacityError::AllocFailed { capacity: len })?;
+    entries.extend((0..len).map(|_| Entry::empty()));
+    Ok(entries.into_boxed_slice())
+}
+
+/// like `try_new_empty_entries`, but panics or aborts like `Box::new` on failure
+fn new_empty_entries<Entry: TableEntry>(len: usize) -> Box<[Entry]> {
+    match try_new_empty_entries(len) {
+        Ok(entries) => entries,
+        Err(CapacityError::TooBig { .. }) => panic!("capacity too big"),
+        Err(CapacityError::AllocFailed { .. }) => {
+            handle_alloc_error(Layout::array::<Entry>(len).expect("layout is known to be valid"))
+        }
+        Err(CapacityError::TooSmall { .. }) => {
+            unreachable!("only `with_capacity_bytes` fails with `TooSmall`")
+        }
+    }
+}
+
+/// why a table with the requested capacity couldn't be created
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CapacityError {
+    /// the capacity rounded up to a power of two, or the size of that many
+    /// entries in bytes, doesn't fit in a `usize`
+    TooBig { capacity: usize },
+    /// the allocator couldn't provide memory for `capacity` entries
+    AllocFailed { capacity: usize },
+    /// `bytes` isn't enough for even one entry, see `HashTable::with_capacity_bytes`
+    TooSmall { bytes: usize },
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CapacityError::TooBig { capacity } => {
+                write!(f, "hash table capacity too big: {}", capacity)
+            }
+            CapacityError::AllocFailed { capacity } => write!(
+                f,
+                "failed to allocate a hash table with capacity {}",
+                capacity
+            ),
+            CapacityError::TooSmall { bytes } => {
+                write!(f, "{} bytes is too small for one hash table entry", bytes)
+            }
+        }
+    }
 }
 
-pub struct HashTable<Entry: TableEntry, BH: BuildHasher> {
-    table: Option<Box<[Entry]>>,
+impl std::error::Error for CapacityError {}
+
+enum TableStorage<Entry> {
+    Boxed(Box<[Entry]>),
+    #[cfg(feature = "memmap2")]
+    Mapped(mmap::MappedEntries<Entry>),
+}
+
+impl<Entry> Deref for TableStorage<Entry> {
+    type Target = [Entry];
+    fn deref(&self) -> &[Entry] {
+        match self {
+            TableStorage::Boxed(entries) => entries,
+            #[cfg(feature = "memmap2")]
+            TableStorage::Mapped(entries) => entries,
+        }
+    }
+}
+
+impl<Entry> DerefMut for TableStorage<Entry> {
+    fn deref_mut(&mut self) -> &mut [Entry] {
+        match self {
+            TableStorage::Boxed(entries) => entries,
+            #[cfg(feature = "memmap2")]
+            TableStorage::Mapped(entries) => entries,
+        }
+    }
+}
+
+pub struct HashTable<Entry: TableEntry, BH: BuildHasher, P: ProbeSequence = LinearProbeSequence> {
+    table: Option<TableStorage<Entry>>,
     hasher: BH,
     insert_search_limit: usize,
+    adaptive_search_limit: bool,
+    len: AtomicUsize,
+    growth_policy: GrowthPolicy,
+    /// the id of the entry in each slot (0 for none) if explicit ids are enabled
+    ids: Option<Box<[AtomicU32]>>,
+    next_id: AtomicU32,
+    #[cfg(feature = "stats")]
+    stats: StatCounters,
+    _probe_sequence: PhantomData<fn() -> P>,
+}
+
+/// counts of operations on a `HashTable`, see `HashTable::stats_snapshot`.
+///
+/// Only counted when the `stats` feature is enabled, otherwise always zero.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HashTableStats {
+    /// inserts that added a new entry
+    pub inserts: usize,
+    /// calls to `find`, `find_full`, and `CachedHashTable::find`, including
+    /// ones answered by the cache
+    pub finds: usize,
+    pub find_misses: usize,
+    /// inserts that failed with `TableFullOrSearchLimitHit`
+    pub search_limit_hits: usize,
+    /// finds answered by a `CachedHashTable`'s cache
+    pub cache_hits: usize,
+}
+
+#[cfg(feature = "stats")]
+#[derive(Default)]
+struct StatCounters {
+    inserts: AtomicUsize,
+    finds: AtomicUsize,
+    find_misses: AtomicUsize,
+    search_limit_hits: AtomicUsize,
+    cache_hits: AtomicUsize,
+}
+
+/// when and by how much `HashTable::insert_or_grow` and `HashTable::reserve` grow the table
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GrowthPolicy {
+    /// the table grows when `len / capacity` would exceed this, must be in `(0, 1]`
+    pub max_load_factor: f64,
+    /// the capacity is multiplied by this when growing, must be a power of two greater than 1
+    pub growth_factor: usize,
+}
+
+impl GrowthPolicy {
+    /// the most entries a table with `capacity` entries can hold
+    fn max_len(self, capacity: usize) -> usize {
+        (capacity as f64 * self.max_load_factor) as usize
+    }
+}
+
+impl Default for GrowthPolicy {
+    fn default() -> Self {
+        Self {
+            max_load_factor: 0.75,
+            growth_factor: 2,
+        }
+    }
+}
+
+/// a shared borrow of a value in a `HashTable`, returned by lookups.
+///
+/// Tables currently never move entries while shared, so this is just a
+/// reference; lookups return it so that the table can later hold off
+/// concurrent structural changes (like resizing) for as long as it's alive
+/// without changing the API.
+#[derive(Debug)]
+pub struct Ref<'a, T: ?Sized> {
+    value: &'a T,
+}
+
+impl<'a, T: ?Sized> Ref<'a, T> {
+    pub(crate) fn new(value: &'a T) -> Self {
+        Ref { value }
+    }
+    /// borrows part of the value, keeping it borrowed from the table
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(orig: Self, f: F) -> Ref<'a, U> {
+        Ref {
+            value: f(orig.value),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for Ref<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.value
+    }
 }
 
 #[derive(Debug)]
@@ -62,6 +339,8 @@ pub enum InsertFailureReason<'a, Value> {
     AlreadyInTable {
         passed_in_value: Value,
         entry_value: &'a Value,
+        /// the explicit id of the entry, if explicit ids are enabled
+        id: Option<NonZeroU32>,
     },
     TableFullOrSearchLimitHit {
         passed_in_value: Value,
@@ -72,6 +351,17 @@ pub enum InsertFailureReason<'a, Value> {
 pub struct GetOrInsertSuccess<'a, Value> {
     passed_in_value: Option<Value>,
     entry_value: &'a Value,
+    id: Option<NonZeroU32>,
+}
+
+impl<'a, Value> GetOrInsertSuccess<'a, Value> {
+    pub fn entry_value(&self) -> &'a Value {
+        self.entry_value
+    }
+    /// the explicit id of the entry, if explicit ids are enabled
+    pub fn id(&self) -> Option<NonZeroU32> {
+        self.id
+    }
 }
 
 #[derive(Debug)]
@@ -79,12 +369,33 @@ pub enum GetOrInsertFailureReason<Value> {
     TableFullOrSearchLimitHit { passed_in_value: Value },
 }
 
-struct TableIndexIter {
+/// the order in which a `HashTable` searches slots for a key.
+///
+/// The sequence is endless, but only its first `capacity` items are used, so
+/// those must include every slot; otherwise a key may not be found in a table
+/// with empty slots left.
+pub trait ProbeSequence: Iterator<Item = usize> {
+    /// `table_index_mask` is the table's capacity (a power of two) minus one
+    fn new(hash: u64, table_index_mask: usize) -> Self;
+}
+
+/// linear probing: starts at the slot picked by the hash and tries each
+/// following slot, wrapping around at the end of the table
+pub struct LinearProbeSequence {
     table_index: usize,
     table_index_mask: usize,
 }
 
-impl Iterator for TableIndexIter {
+impl ProbeSequence for LinearProbeSequence {
+    fn new(hash: u64, table_index_mask: usize) -> Self {
+        Self {
+            table_index: hash as usize & table_index_mask,
+            table_index_mask,
+        }
+    }
+}
+
+impl Iterator for LinearProbeSequence {
     type Item = usize;
     fn next(&mut self) -> Option<usize> {
         let retval = self.table_index;
@@ -93,14 +404,124 @@ impl Iterator for TableIndexIter {
     }
 }
 
+/// quadratic probing: tries the slot picked by the hash, then the slots
+/// 1, 3, 6, 10, ... (the triangular numbers) after it, wrapping around at the
+/// end of the table. This visits every slot of a power-of-two sized table
+/// and spreads out collisions, avoiding the long clusters linear probing
+/// builds up at high load, at the cost of worse memory locality.
+pub struct QuadraticProbeSequence {
+    table_index: usize,
+    step: usize,
+    table_index_mask: usize,
+}
+
+impl ProbeSequence for QuadraticProbeSequence {
+    fn new(hash: u64, table_index_mask: usize) -> Self {
+        Self {
+            table_index: hash as usize & table_index_mask,
+            step: 0,
+            table_index_mask,
+        }
+    }
+}
+
+impl Iterator for QuadraticProbeSequence {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        let retval = self.table_index;
+        self.step = self.step.wrapping_add(1);
+        self.table_index = self.table_index.wrapping_add(self.step) & self.table_index_mask;
+        Some(retval)
+    }
+}
+
+/// how many slots lookups of the keys in a table have to look at
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ProbeLengthStats {
+    pub max: usize,
+    pub mean: f64,
+}
+
+impl ProbeLengthStats {
+    fn from_probe_lengths(probe_lengths: impl Iterator<Item = usize>) -> Self {
+        let mut max = 0;
+        let mut total = 0;
+        let mut count = 0;
+        for probe_length in probe_lengths {
+            max = max.max(probe_length);
+            total += probe_length;
+            count += 1;
+        }
+        let mean = if count == 0 {
+            0.0
+        } else {
+            total as f64 / count as f64
+        };
+        Self { max, mean }
+    }
+}
+
+/// the slots an insert of a key would look at, see `HashTable::probe_report`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeReport {
+    pub search_limit: usize,
+    pub probes: Vec<Probe>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Probe {
+    pub table_index: usize,
+    pub slot: ProbedSlot,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProbedSlot {
+    Empty,
+    /// `id` is the entry's explicit id, if explicit ids are enabled
+    Occupied {
+        key: Key,
+        id: Option<NonZeroU32>,
+    },
+}
+
+/// the smallest limit the adaptive search limit will use
+const MIN_ADAPTIVE_SEARCH_LIMIT: usize = 8;
+
+/// Computes the insert search limit from the table's load factor `a = len / capacity`.
+///
+/// The expected probe length of an unsuccessful linear-probing search is
+/// `(1 + 1 / (1 - a)^2) / 2`; this allows 4 times that, computed with
+/// `1 / (1 - a)` rounded up to an integer, and clamped to
+/// `MIN_ADAPTIVE_SEARCH_LIMIT..=capacity`.
+fn adaptive_search_limit(len: usize, capacity: usize) -> usize {
+    let free = capacity.saturating_sub(len);
+    if free == 0 {
+        return capacity;
+    }
+    let inverse_free_fraction = capacity.div_ceil(free);
+    inverse_free_fraction
+        .saturating_mul(inverse_free_fraction)
+        .saturating_add(1)
+        .saturating_mul(2)
+        .max(MIN_ADAPTIVE_SEARCH_LIMIT)
+        .min(capacity)
+}
+
 pub struct HashTableDrain<'a, Entry: TableEntry> {
     entry_iter: std::slice::IterMut<'a, Entry>,
+    len: &'a mut usize,
 }
 
 impl<Entry: TableEntry> Iterator for HashTableDrain<'_, Entry> {
     type Item = (Key, Entry::Values);
     fn next(&mut self) -> Option<(Key, Entry::Values)> {
-        self.entry_iter.next().and_then(TableEntry::take)
+        for entry in &mut self.entry_iter {
+            if let Some(retval) = entry.take() {
+                *self.len -= 1;
+                return Some(retval);
+            }
+        }
+        None
     }
 }
 
@@ -110,6 +531,68 @@ impl<Entry: TableEntry> Drop for HashTableDrain<'_, Entry> {
     }
 }
 
+pub struct HashTableIntoDrain<Entry: TableEntry, BH: BuildHasher, P: ProbeSequence> {
+    table: HashTable<Entry, BH, P>,
+    table_index: usize,
+}
+
+impl<Entry: TableEntry, BH: BuildHasher, P: ProbeSequence> Iterator
+    for HashTableIntoDrain<Entry, BH, P>
+{
+    type Item = (Key, Entry::Values);
+    fn next(&mut self) -> Option<(Key, Entry::Values)> {
+        let entries = self
+            .table
+            .table
+            .as_mut()
+            .expect("table is known to be Some");
+        while let Some(entry) = entries.get_mut(self.table_index) {
+            self.table_index += 1;
+            if let Some(retval) = entry.take() {
+                *self.table.len.get_mut() -= 1;
+                return Some(retval);
+            }
+        }
+        None
+    }
+}
+
+/// the result of `HashTable::entry`
+pub enum HashTableEntry<'a, Entry: TableEntry, BH: BuildHasher, P: ProbeSequence> {
+    Occupied(&'a Entry::Values),
+    Vacant(VacantEntry<'a, Entry, BH, P>),
+}
+
+/// a key that wasn't in the table when `HashTable::entry` looked for it.
+///
+/// No slot is reserved, so other threads may insert the key in the meantime.
+pub struct VacantEntry<'a, Entry: TableEntry, BH: BuildHasher, P: ProbeSequence> {
+    table: &'a HashTable<Entry, BH, P>,
+    key: Key,
+}
+
+impl<'a, Entry: TableEntry, BH: BuildHasher, P: ProbeSequence> VacantEntry<'a, Entry, BH, P> {
+    pub fn key(&self) -> Key {
+        self.key
+    }
+    /// inserts `value` for the key, returning the values now in the table. If
+    /// another thread inserted the key first, its values are returned instead
+    /// and `value` is dropped.
+    pub fn insert(
+        self,
+        value: Entry::Values,
+    ) -> Result<&'a Entry::Values, GetOrInsertFailureReason<Entry::Values>> {
+        match self.table.insert(self.key, value) {
+            Ok(entry_value) | Err(InsertFailureReason::AlreadyInTable { entry_value, .. }) => {
+                Ok(entry_value)
+            }
+            Err(InsertFailureReason::TableFullOrSearchLimitHit { passed_in_value }) => {
+                Err(GetOrInsertFailureReason::TableFullOrSearchLimitHit { passed_in_value })
+            }
+        }
+    }
+}
+
 pub struct HashTableIter<'a, Entry: TableEntry> {
     entry_iter: std::slice::Iter<'a, Entry>,
 }
@@ -117,28 +600,127 @@ pub struct HashTableIter<'a, Entry: TableEntry> {
 impl<'a, Entry: TableEntry> Iterator for HashTableIter<'a, Entry> {
     type Item = (Key, &'a Entry::Values);
     fn next(&mut self) -> Option<(Key, &'a Entry::Values)> {
-        self.entry_iter.next().and_then(TableEntry::get)
+        self.entry_iter.find_map(TableEntry::get)
     }
 }
 
-impl<Entry: TableEntry, BH: BuildHasher> HashTable<Entry, BH> {
+impl<Entry: TableEntry, BH: BuildHasher, P: ProbeSequence> HashTable<Entry, BH, P> {
     pub fn with_search_limit_and_hasher(
-        mut capacity: usize,
+        capacity: usize,
         insert_search_limit: usize,
         hasher: BH,
     ) -> Self {
-        capacity = capacity
+        let capacity = capacity
             .checked_next_power_of_two()
             .expect("capacity too big");
+        Self::from_entries(new_empty_entries(capacity), insert_search_limit, hasher)
+    }
+    /// like `with_search_limit_and_hasher`, but returns an error instead of
+    /// panicking or aborting when `capacity` is too big or can't be
+    /// allocated, for when `capacity` comes from untrusted input
+    pub fn try_with_capacity(
+        capacity: usize,
+        insert_search_limit: usize,
+        hasher: BH,
+    ) -> Result<Self, CapacityError> {
+        let capacity = capacity
+            .checked_next_power_of_two()
+            .ok_or(CapacityError::TooBig { capacity })?;
+        Ok(Self::from_entries(
+            try_new_empty_entries(capacity)?,
+            insert_search_limit,
+            hasher,
+        ))
+    }
+    fn from_entries(entries: Box<[Entry]>, insert_search_limit: usize, hasher: BH) -> Self {
         Self {
-            table: Some((0..capacity).map(|_| Entry::empty()).collect()),
+            table: Some(TableStorage::Boxed(entries)),
             hasher,
             insert_search_limit,
+            adaptive_search_limit: false,
+            len: AtomicUsize::new(0),
+            growth_policy: GrowthPolicy::default(),
+            ids: None,
+            next_id: AtomicU32::new(1),
+            #[cfg(feature = "stats")]
+            stats: StatCounters::default(),
+            _probe_sequence: PhantomData,
         }
     }
+    /// Creates a table whose entries are stored in the memory-mapped `file`,
+    /// growing `file` if it's too small.
+    /// Fails if `Entry::zeroed_is_empty()` is `None`.
+    ///
+    /// Values left in the table aren't dropped when the table is dropped,
+    /// they are flushed to `file` instead. Values moved out of the table, such
+    /// as by `drain`, `clear`, `remove` or `retain`, are dropped as usual.
+    ///
+    /// The size of `file` is fixed, so operations that would move the entries
+    /// to a table with a different capacity (`reserve`, `insert_or_grow` and
+    /// `shrink_to_fit` when they would resize) panic instead. `retain` keeps
+    /// the entries in `file`.
+    ///
+    /// # Safety
+    ///
+    /// `file` must either be zero-filled (such as a newly created file), or
+    /// have last been used by a table with the same `Entry` type and capacity
+    /// that was dropped normally. `hasher` must hash keys the same way as that
+    /// table's hasher did, otherwise existing entries won't be found, so use a
+    /// deterministic hasher.
+    ///
+    /// `Entry::Values` is read back from the bytes written to `file`, possibly
+    /// by another process, so its early and late values must be plain data
+    /// that's still valid there: no references, pointers (including `Box`,
+    /// `Vec` and `String`), or anything else that refers to memory outside
+    /// the entry, and nothing that needs to be dropped.
+    #[cfg(feature = "memmap2")]
+    pub unsafe fn with_file(
+        file: &File,
+        capacity: usize,
+        insert_search_limit: usize,
+        hasher: BH,
+    ) -> io::Result<Self> {
+        let capacity = capacity
+            .checked_next_power_of_two()
+            .expect("capacity too big");
+        let table = mmap::MappedEntries::<Entry>::new(file, capacity)?;
+        let len = table.iter().filter(|entry| entry.get().is_some()).count();
+        Ok(Self {
+            table: Some(TableStorage::Mapped(table)),
+            hasher,
+            insert_search_limit,
+            adaptive_search_limit: false,
+            len: AtomicUsize::new(len),
+            growth_policy: GrowthPolicy::default(),
+            ids: None,
+            next_id: AtomicU32::new(1),
+            #[cfg(feature = "stats")]
+            stats: StatCounters::default(),
+            _probe_sequence: PhantomData,
+        })
+    }
     pub fn with_hasher(capacity: usize, hasher: BH) -> Self {
         Self::with_search_limit_and_hasher(capacity, 32, hasher)
     }
+    /// creates the largest table whose entries fit in `bytes` bytes.
+    /// Fails if `bytes` is too small for even one entry, or if the entries
+    /// can't be allocated. Zero-sized entries fail with `TooBig`, since any
+    /// number of them fit.
+    ///
+    /// Only the entries are counted, not the memory used by explicit ids.
+    pub fn with_capacity_bytes(bytes: usize, hasher: BH) -> Result<Self, CapacityError> {
+        let max_capacity =
+            bytes
+                .checked_div(std::mem::size_of::<Entry>())
+                .ok_or(CapacityError::TooBig {
+                    capacity: usize::MAX,
+                })?;
+        if max_capacity == 0 {
+            return Err(CapacityError::TooSmall { bytes });
+        }
+        let capacity = 1 << (usize::BITS - 1 - max_capacity.leading_zeros());
+        Self::try_with_capacity(capacity, 32, hasher)
+    }
     pub fn with_search_limit(capacity: usize, insert_search_limit: usize) -> Self
     where
         BH: Default,
@@ -151,54 +733,212 @@ impl<Entry: TableEntry, BH: BuildHasher> HashTable<Entry, BH> {
     {
         Self::with_hasher(capacity, BH::default())
     }
+    /// the smallest (power of two) capacity that holds `expected_entries`
+    /// entries at a load factor of at most `target_load`
+    pub fn capacity_for(expected_entries: usize, target_load: f64) -> usize {
+        assert!(
+            target_load > 0.0 && target_load <= 1.0,
+            "invalid target_load"
+        );
+        let mut capacity = 1usize;
+        while ((capacity as f64 * target_load) as usize) < expected_entries {
+            capacity = capacity.checked_mul(2).expect("capacity too big");
+        }
+        capacity
+    }
     fn get_table(&self) -> &[Entry] {
         self.table.as_ref().expect("table is known to be Some")
     }
-    fn get_table_mut(&mut self) -> &mut [Entry] {
-        self.table.as_mut().expect("table is known to be Some")
-    }
     pub fn capacity(&self) -> usize {
         self.get_table().len()
     }
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// `len() / capacity()`
+    pub fn load_factor(&self) -> f64 {
+        self.len() as f64 / self.capacity() as f64
+    }
     pub fn hasher(&self) -> &BH {
         &self.hasher
     }
+    /// the search limit used by the next insert; see `enable_adaptive_search_limit`
     pub fn insert_search_limit(&self) -> usize {
-        self.insert_search_limit
+        if self.adaptive_search_limit {
+            adaptive_search_limit(self.len(), self.capacity())
+        } else {
+            self.insert_search_limit
+        }
     }
+    /// sets a fixed search limit, disabling the adaptive search limit
     pub fn set_insert_search_limit(&mut self, insert_search_limit: usize) {
         self.insert_search_limit = insert_search_limit;
+        self.adaptive_search_limit = false;
+    }
+    /// derive the search limit from the current load factor on each insert,
+    /// allowing longer probing as the table fills up.
+    /// See `adaptive_search_limit` for the formula.
+    pub fn enable_adaptive_search_limit(&mut self) {
+        self.adaptive_search_limit = true;
+    }
+    pub fn is_adaptive_search_limit_enabled(&self) -> bool {
+        self.adaptive_search_limit
     }
     fn table_indexes(&self, key: Key, limit: usize) -> impl Iterator<Item = usize> {
         let mut hasher = self.hasher.build_hasher();
         key.hash(&mut hasher);
-        let table_index_mask = self.capacity() - 1;
-        let table_index = hasher.finish() as usize & table_index_mask;
-        TableIndexIter {
-            table_index,
-            table_index_mask,
+        P::new(hasher.finish(), self.capacity() - 1).take(self.capacity().min(limit))
+    }
+    pub fn find(&self, key: Key) -> Option<Ref<Entry::Values>> {
+        count_stat!(self, finds);
+        let retval = self.find_quiet(key);
+        if retval.is_none() {
+            count_stat!(self, find_misses);
         }
-        .take(self.capacity().min(limit))
+        retval
     }
-    pub fn find(&self, key: Key) -> Option<&Entry::Values> {
+    /// the operation counts since the table was created or `reset_stats` was
+    /// last called; all zero unless the `stats` feature is enabled
+    #[cfg(feature = "stats")]
+    pub fn stats_snapshot(&self) -> HashTableStats {
+        let StatCounters {
+            inserts,
+            finds,
+            find_misses,
+            search_limit_hits,
+            cache_hits,
+        } = &self.stats;
+        HashTableStats {
+            inserts: inserts.load(Ordering::Relaxed),
+            finds: finds.load(Ordering::Relaxed),
+            find_misses: find_misses.load(Ordering::Relaxed),
+            search_limit_hits: search_limit_hits.load(Ordering::Relaxed),
+            cache_hits: cache_hits.load(Ordering::Relaxed),
+        }
+    }
+    #[cfg(not(feature = "stats"))]
+    pub fn stats_snapshot(&self) -> HashTableStats {
+        HashTableStats::default()
+    }
+    pub fn reset_stats(&self) {
+        #[cfg(feature = "stats")]
+        {
+            let StatCounters {
+                inserts,
+                finds,
+                find_misses,
+                search_limit_hits,
+                cache_hits,
+            } = &self.stats;
+            for counter in &[inserts, finds, find_misses, search_limit_hits, cache_hits] {
+                counter.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+    /// like `find`, but for internal bulk operations (such as GC traversals):
+    /// never updates any statistics, so it doesn't skew user-visible metrics
+    pub fn find_quiet(&self, key: Key) -> Option<Ref<Entry::Values>> {
+        self.find_entry(key)
+            .map(|(_, entry_value)| Ref::new(entry_value))
+    }
+    /// looks up `key`, for constructing its values only when it's missing
+    pub fn entry(&self, key: Key) -> HashTableEntry<Entry, BH, P> {
+        match self.find_entry(key) {
+            Some((_, entry_value)) => HashTableEntry::Occupied(entry_value),
+            None => HashTableEntry::Vacant(VacantEntry { table: self, key }),
+        }
+    }
+    /// like `find(key).is_some()`, without borrowing the values
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.find_entry(key).is_some()
+    }
+    /// the slot holding `key` and its values, if any
+    fn find_entry(&self, key: Key) -> Option<(usize, &Entry::Values)> {
         let table = self.get_table();
         for table_index in self.table_indexes(key, usize::max_value()) {
-            let (entry_key, entry_value) = table[table_index].get()?;
+            let entry = &table[table_index];
+            if let Some(key00) = entry.peek_key00() {
+                if key00 != key.0[0][0] {
+                    continue;
+                }
+            }
+            let (entry_key, entry_value) = entry.get()?;
+            if entry_key == key {
+                return Some((table_index, entry_value));
+            }
+        }
+        None
+    }
+    /// like `find_quiet`, but reads entries with `get_unsynchronized`, for
+    /// bulk read-only traversals. Never updates any statistics.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may be inserting into this table, and every insert
+    /// must happen-before this call; see `TableEntry::get_unsynchronized`.
+    pub unsafe fn find_unsynchronized(&self, key: Key) -> Option<Ref<Entry::Values>> {
+        let table = self.get_table();
+        for table_index in self.table_indexes(key, usize::MAX) {
+            let (entry_key, entry_value) = table[table_index].get_unsynchronized()?;
             if entry_key == key {
-                return Some(entry_value);
+                return Some(Ref::new(entry_value));
             }
         }
         None
     }
+    /// looks up both the early and late values in one lookup
+    #[allow(clippy::type_complexity)]
+    pub fn find_full(
+        &self,
+        key: Key,
+    ) -> Option<(
+        Ref<<Entry::Values as TableEntryValues>::EarlyValue>,
+        Option<<Entry::Values as TableEntryValues>::LateValue>,
+    )> {
+        let values = self.find(key)?;
+        let late_value = values.late_value();
+        Some((Ref::map(values, TableEntryValues::early_value), late_value))
+    }
     pub fn insert(
         &self,
         key: Key,
-        mut value: Entry::Values,
+        value: Entry::Values,
+    ) -> Result<&Entry::Values, InsertFailureReason<Entry::Values>> {
+        self.insert_at(key, value, self.insert_search_limit())
+            .map(|(_, entry_value)| entry_value)
+    }
+    /// like `insert`, but probes at most `limit` slots instead of
+    /// `insert_search_limit()`, for this call only
+    pub fn insert_with_limit(
+        &self,
+        key: Key,
+        value: Entry::Values,
+        limit: usize,
     ) -> Result<&Entry::Values, InsertFailureReason<Entry::Values>> {
+        self.insert_at(key, value, limit)
+            .map(|(_, entry_value)| entry_value)
+    }
+    /// like `insert_with_limit`, but also returns the slot the new entry is in
+    fn insert_at(
+        &self,
+        key: Key,
+        mut value: Entry::Values,
+        limit: usize,
+    ) -> Result<(usize, &Entry::Values), InsertFailureReason<Entry::Values>> {
         let table = self.get_table();
-        for table_index in self.table_indexes(key, self.insert_search_limit) {
+        for table_index in self.table_indexes(key, limit) {
             match table[table_index].fill(key, value) {
-                Ok(entry_value) => return Ok(entry_value),
+                Ok(entry_value) => {
+                    self.len.fetch_add(1, Ordering::Relaxed);
+                    count_stat!(self, inserts);
+                    if let Some(ids) = &self.ids {
+                        ids[table_index].store(self.new_id().get(), Ordering::Release);
+                    }
+                    return Ok((table_index, entry_value));
+                }
                 Err(AlreadyFull {
                     passed_in_value,
                     entry_key,
@@ -208,66 +948,527 @@ impl<Entry: TableEntry, BH: BuildHasher> HashTable<Entry, BH> {
                         return Err(InsertFailureReason::AlreadyInTable {
                             entry_value,
                             passed_in_value,
+                            id: self.explicit_id_at(table_index),
                         });
                     }
                     value = passed_in_value;
                 }
             }
         }
+        count_stat!(self, search_limit_hits);
         Err(InsertFailureReason::TableFullOrSearchLimitHit {
             passed_in_value: value,
         })
     }
+    /// gives each entry an id that stays the same when the entry moves to a
+    /// different slot (such as when the table grows), assigned in increasing
+    /// order as entries are inserted. Entries already in the table get ids in
+    /// slot order. Costs 4 bytes per slot.
+    pub fn enable_explicit_ids(&mut self) {
+        if self.ids.is_some() {
+            return;
+        }
+        let ids: Box<[AtomicU32]> = (0..self.capacity()).map(|_| AtomicU32::new(0)).collect();
+        for (entry, id) in self.get_table().iter().zip(ids.iter()) {
+            if entry.get().is_some() {
+                id.store(self.new_id().get(), Ordering::Relaxed);
+            }
+        }
+        self.ids = Some(ids);
+    }
+    pub fn has_explicit_ids(&self) -> bool {
+        self.ids.is_some()
+    }
+    fn new_id(&self) -> NonZeroU32 {
+        NonZeroU32::new(self.next_id.fetch_add(1, Ordering::Relaxed)).expect("ran out of ids")
+    }
+    /// the id of the entry in `table_index`, waiting for it to be stored if
+    /// another thread just inserted the entry
+    fn wait_for_id(&self, table_index: usize) -> NonZeroU32 {
+        let ids = self.ids.as_ref().expect("explicit ids are not enabled");
+        loop {
+            if let Some(id) = NonZeroU32::new(ids[table_index].load(Ordering::Acquire)) {
+                return id;
+            }
+            spin_loop_hint();
+        }
+    }
+    /// the explicit id of `key`'s entry; panics if explicit ids are not enabled
+    pub fn id_of(&self, key: Key) -> Option<NonZeroU32> {
+        assert!(self.has_explicit_ids(), "explicit ids are not enabled");
+        let (table_index, _) = self.find_entry(key)?;
+        Some(self.wait_for_id(table_index))
+    }
+    /// the explicit ids of all entries, in slot order; panics if explicit ids
+    /// are not enabled. Entries another thread is still inserting may be
+    /// missed.
+    pub fn explicit_ids(&self) -> impl Iterator<Item = NonZeroU32> + '_ {
+        self.ids
+            .as_ref()
+            .expect("explicit ids are not enabled")
+            .iter()
+            .filter_map(|id| NonZeroU32::new(id.load(Ordering::Acquire)))
+    }
+    /// like `iter`, but also yields each entry's explicit id; panics if
+    /// explicit ids are not enabled. Entries another thread is still
+    /// inserting may be missed.
+    pub fn iter_with_ids(&self) -> impl Iterator<Item = (NonZeroU32, Key, &Entry::Values)> {
+        let ids = self.ids.as_ref().expect("explicit ids are not enabled");
+        self.get_table()
+            .iter()
+            .zip(ids.iter())
+            .filter_map(|(entry, id)| {
+                let id = NonZeroU32::new(id.load(Ordering::Acquire))?;
+                let (key, values) = entry.get()?;
+                Some((id, key, values))
+            })
+    }
+    /// the explicit id of the entry in `table_index`, if explicit ids are enabled
+    fn explicit_id_at(&self, table_index: usize) -> Option<NonZeroU32> {
+        self.ids.as_ref().map(|_| self.wait_for_id(table_index))
+    }
+    /// the key of the entry with explicit id `id`, for debugging; panics if
+    /// explicit ids are not enabled. Scans the whole table, so this is slow.
+    /// Only available in debug builds or with the `debug-ids` feature.
+    #[cfg(any(debug_assertions, feature = "debug-ids"))]
+    pub fn key_for_id(&self, id: NonZeroU32) -> Option<Key> {
+        self.iter_with_ids()
+            .find(|&(entry_id, _, _)| entry_id == id)
+            .map(|(_, key, _)| key)
+    }
+    /// like `get_or_insert`, but returns the explicit id of `key`'s entry;
+    /// panics if explicit ids are not enabled
+    pub fn intern(
+        &self,
+        key: Key,
+        value: Entry::Values,
+    ) -> Result<NonZeroU32, GetOrInsertFailureReason<Entry::Values>> {
+        assert!(self.has_explicit_ids(), "explicit ids are not enabled");
+        let success = self.get_or_insert(key, value)?;
+        Ok(success.id.expect("explicit ids are known to be enabled"))
+    }
+    /// `GetOrInsertSuccess::id` is the entry's explicit id if explicit ids
+    /// are enabled
     pub fn get_or_insert(
         &self,
         key: Key,
         value: Entry::Values,
     ) -> Result<GetOrInsertSuccess<Entry::Values>, GetOrInsertFailureReason<Entry::Values>> {
-        match self.insert(key, value) {
-            Ok(entry_value) => Ok(GetOrInsertSuccess {
+        match self.insert_at(key, value, self.insert_search_limit()) {
+            Ok((table_index, entry_value)) => Ok(GetOrInsertSuccess {
                 entry_value,
                 passed_in_value: None,
+                id: self.explicit_id_at(table_index),
             }),
             Err(InsertFailureReason::AlreadyInTable {
                 entry_value,
                 passed_in_value,
+                id,
             }) => Ok(GetOrInsertSuccess {
                 entry_value,
                 passed_in_value: Some(passed_in_value),
+                id,
             }),
             Err(InsertFailureReason::TableFullOrSearchLimitHit { passed_in_value }) => {
                 Err(GetOrInsertFailureReason::TableFullOrSearchLimitHit { passed_in_value })
             }
         }
     }
-    pub fn drain(&mut self) -> HashTableDrain<Entry> {
-        HashTableDrain {
-            entry_iter: self.get_table_mut().iter_mut(),
+    /// rehashes all entries into the smallest table that holds them at a load
+    /// factor of at most 3/4, returning the new capacity.
+    ///
+    /// Entries are moved to new slots, so anything derived from slot positions
+    /// must be recomputed afterward.
+    pub fn shrink_to_fit(&mut self) -> usize {
+        let new_capacity = Self::capacity_for(self.len(), 0.75);
+        if new_capacity < self.capacity() {
+            self.rehash(new_capacity);
         }
+        self.capacity()
     }
-    pub fn iter(&self) -> HashTableIter<Entry> {
-        HashTableIter {
-            entry_iter: self.get_table().iter(),
+    /// moves all entries into a new table with `new_capacity` entries, which
+    /// must be a power of two that can hold all entries
+    fn rehash(&mut self, new_capacity: usize) {
+        self.rehash_retain(new_capacity, |_, _| true);
+    }
+    /// like `rehash`, but drops the entries `keep` returns false for,
+    /// returning how many were dropped
+    fn rehash_retain(
+        &mut self,
+        new_capacity: usize,
+        mut keep: impl FnMut(Key, &Entry::Values) -> bool,
+    ) -> usize {
+        let mut removed = 0;
+        let mut old_table = match self.table.as_mut().expect("table is known to be Some") {
+            #[cfg(feature = "memmap2")]
+            TableStorage::Mapped(entries) => {
+                // the entries have to stay in the file, so move them out to a
+                // temporary table and then back into the emptied mapping
+                assert_eq!(
+                    new_capacity,
+                    entries.len(),
+                    "can't resize a table stored in a file"
+                );
+                let mut old_entries = new_empty_entries::<Entry>(new_capacity);
+                for (entry, old_entry) in entries.iter_mut().zip(old_entries.iter_mut()) {
+                    if let Some((key, value)) = entry.take() {
+                        assert!(old_entry.fill(key, value).is_ok());
+                    }
+                }
+                TableStorage::Boxed(old_entries)
+            }
+            table => std::mem::replace(table, TableStorage::Boxed(new_empty_entries(new_capacity))),
+        };
+        let mut old_ids = self.ids.take();
+        if old_ids.is_some() {
+            self.ids = Some((0..new_capacity).map(|_| AtomicU32::new(0)).collect());
+        }
+        for (old_index, entry) in old_table.iter_mut().enumerate() {
+            if let Some((key, value)) = entry.take() {
+                if !keep(key, &value) {
+                    removed += 1;
+                    continue;
+                }
+                let table_index = self.fill_any_slot(key, value);
+                if let (Some(old_ids), Some(ids)) = (&mut old_ids, &self.ids) {
+                    ids[table_index].store(*old_ids[old_index].get_mut(), Ordering::Relaxed);
+                }
+            }
         }
+        *self.len.get_mut() -= removed;
+        removed
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::sync::atomic::AtomicUsize;
-    use std::sync::atomic::Ordering;
-    use std::sync::Arc;
-
-    #[derive(Debug)]
-    struct DropCounter {
-        drop_count: Arc<AtomicUsize>,
+    /// drops the entries `keep` returns false for, returning how many were
+    /// dropped. Kept entries keep their explicit ids but may move to other
+    /// slots.
+    pub fn retain(&mut self, keep: impl FnMut(Key, &Entry::Values) -> bool) -> usize {
+        self.rehash_retain(self.capacity(), keep)
     }
-
-    impl Drop for DropCounter {
-        fn drop(&mut self) {
-            let value = self.drop_count.fetch_add(1, Ordering::Relaxed);
-            println!("DropCounter::drop: {}", value);
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.growth_policy
+    }
+    pub fn set_growth_policy(&mut self, growth_policy: GrowthPolicy) {
+        assert!(
+            growth_policy.max_load_factor > 0.0 && growth_policy.max_load_factor <= 1.0,
+            "invalid max_load_factor"
+        );
+        assert!(
+            growth_policy.growth_factor.is_power_of_two() && growth_policy.growth_factor > 1,
+            "invalid growth_factor"
+        );
+        self.growth_policy = growth_policy;
+    }
+    /// grows the table, as specified by the growth policy, until `additional`
+    /// more entries can be inserted without exceeding the maximum load factor.
+    ///
+    /// Growing moves entries to new slots, so anything derived from slot
+    /// positions must be recomputed afterward.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed_len = self
+            .len()
+            .checked_add(additional)
+            .expect("capacity too big");
+        let mut new_capacity = self.capacity();
+        while self.growth_policy.max_len(new_capacity) < needed_len {
+            new_capacity = new_capacity
+                .checked_mul(self.growth_policy.growth_factor)
+                .expect("capacity too big");
+        }
+        if new_capacity != self.capacity() {
+            self.rehash(new_capacity);
+        }
+    }
+    /// `true` if inserting `key` would find either `key` or an empty slot
+    /// within the insert search limit
+    fn has_slot_for(&self, key: Key) -> bool {
+        let table = self.get_table();
+        self.table_indexes(key, self.insert_search_limit())
+            .any(|table_index| match table[table_index].get() {
+                None => true,
+                Some((entry_key, _)) => entry_key == key,
+            })
+    }
+    /// like `insert`, but grows the table as specified by the growth policy
+    /// instead of failing when the table is full or the search limit is hit.
+    ///
+    /// Grows at most once more than `reserve(1)` would: if that doesn't make
+    /// room for `key`, its probe sequence is clustered (such as with a bad
+    /// hasher or a search limit of 0), which growing further won't fix, so
+    /// this fails with `TableFullOrSearchLimitHit` instead.
+    ///
+    /// Growing moves entries to new slots, so anything derived from slot
+    /// positions must be recomputed afterward.
+    pub fn insert_or_grow(
+        &mut self,
+        key: Key,
+        value: Entry::Values,
+    ) -> Result<&Entry::Values, InsertFailureReason<Entry::Values>> {
+        self.reserve(1);
+        if !self.has_slot_for(key) {
+            let new_capacity = self
+                .capacity()
+                .checked_mul(self.growth_policy.growth_factor)
+                .expect("capacity too big");
+            self.rehash(new_capacity);
+        }
+        self.insert(key, value)
+    }
+    /// like `insert_or_grow`, but if `key` is already in the table, its values
+    /// are replaced in the same slot and the old values are returned
+    pub fn insert_or_replace(
+        &mut self,
+        key: Key,
+        value: Entry::Values,
+    ) -> Result<Option<Entry::Values>, GetOrInsertFailureReason<Entry::Values>> {
+        if let Some((table_index, _)) = self.find_entry(key) {
+            let entry = &mut self.table.as_mut().expect("table is known to be Some")[table_index];
+            let (_, old_value) = entry.take().expect("entry is known to be full");
+            if entry.fill(key, value).is_err() {
+                unreachable!("entry was just emptied");
+            }
+            return Ok(Some(old_value));
+        }
+        match self.insert_or_grow(key, value) {
+            Ok(_) => Ok(None),
+            Err(InsertFailureReason::AlreadyInTable { .. }) => {
+                unreachable!("key is known to not be in the table")
+            }
+            Err(InsertFailureReason::TableFullOrSearchLimitHit { passed_in_value }) => {
+                Err(GetOrInsertFailureReason::TableFullOrSearchLimitHit { passed_in_value })
+            }
+        }
+    }
+    /// fills the first empty slot in `key`'s probe sequence; only for use when
+    /// `key` is known to not be in the table and there is an empty slot.
+    /// Returns the filled slot.
+    fn fill_any_slot(&self, key: Key, mut value: Entry::Values) -> usize {
+        let table = self.get_table();
+        for table_index in self.table_indexes(key, usize::MAX) {
+            match table[table_index].fill(key, value) {
+                Ok(_) => return table_index,
+                Err(AlreadyFull {
+                    passed_in_value, ..
+                }) => value = passed_in_value,
+            }
+        }
+        unreachable!("table is known to have an empty slot");
+    }
+    /// lists the slots an insert of `key` would look at, in order, for
+    /// debugging inserts that fail with `TableFullOrSearchLimitHit`. Stops at
+    /// the first empty slot, at `key`'s own entry, or at the search limit.
+    pub fn probe_report(&self, key: Key) -> ProbeReport {
+        let table = self.get_table();
+        let search_limit = self.insert_search_limit();
+        let mut probes = Vec::new();
+        for table_index in self.table_indexes(key, search_limit) {
+            let slot = match table[table_index].get() {
+                None => ProbedSlot::Empty,
+                Some((entry_key, _)) => ProbedSlot::Occupied {
+                    key: entry_key,
+                    id: self
+                        .ids
+                        .as_ref()
+                        .and_then(|ids| NonZeroU32::new(ids[table_index].load(Ordering::Acquire))),
+                },
+            };
+            probes.push(Probe { table_index, slot });
+            match slot {
+                ProbedSlot::Occupied { key: entry_key, .. } if entry_key != key => {}
+                _ => break,
+            }
+        }
+        ProbeReport {
+            search_limit,
+            probes,
+        }
+    }
+    /// how many probes finding each entry takes, in slot order
+    fn probe_lengths(&self) -> impl Iterator<Item = usize> + '_ {
+        self.get_table()
+            .iter()
+            .enumerate()
+            .filter_map(move |(entry_index, entry)| {
+                let (key, _) = entry.get()?;
+                self.table_indexes(key, usize::MAX)
+                    .position(|table_index| table_index == entry_index)
+                    .map(|position| position + 1)
+            })
+    }
+    /// walks the probe sequence of every key in the table, so this is slow
+    pub fn probe_length_stats(&self) -> ProbeLengthStats {
+        ProbeLengthStats::from_probe_lengths(self.probe_lengths())
+    }
+    /// `histogram[d]` is the number of entries `d` probes past the first slot
+    /// their probe sequence tries, for seeing how the whole table's
+    /// displacements are distributed, such as when picking a growth
+    /// threshold. The last element is always nonzero unless the table is
+    /// empty. Walks the probe sequence of every key, so this is slow.
+    pub fn fill_ratio_histogram(&self) -> Vec<usize> {
+        let mut histogram = Vec::new();
+        for probe_length in self.probe_lengths() {
+            let displacement = probe_length - 1;
+            if histogram.len() <= displacement {
+                histogram.resize(displacement + 1, 0);
+            }
+            histogram[displacement] += 1;
+        }
+        histogram
+    }
+    /// removes and drops all entries, keeping the same allocation
+    pub fn clear(&mut self) {
+        self.drain().for_each(std::mem::drop);
+    }
+    pub fn drain(&mut self) -> HashTableDrain<Entry> {
+        if let Some(ids) = &mut self.ids {
+            for id in ids.iter_mut() {
+                *id.get_mut() = 0;
+            }
+        }
+        HashTableDrain {
+            entry_iter: self
+                .table
+                .as_mut()
+                .expect("table is known to be Some")
+                .iter_mut(),
+            len: self.len.get_mut(),
+        }
+    }
+    /// like `drain`, but consumes the table. Taking `self` by value means
+    /// every other thread must be done with the table, so on the sync backend
+    /// this is how to empty a shared table once the threads using it have
+    /// been joined. Entries not yielded are dropped with the iterator.
+    pub fn into_drained(self) -> HashTableIntoDrain<Entry, BH, P> {
+        HashTableIntoDrain {
+            table: self,
+            table_index: 0,
+        }
+    }
+    pub fn iter(&self) -> HashTableIter<Entry> {
+        HashTableIter {
+            entry_iter: self.get_table().iter(),
+        }
+    }
+    /// like `iter`, but in order of increasing key instead of slot order, so
+    /// the order doesn't depend on the hasher. Sorts all entries up front.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (Key, &Entry::Values)> {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_unstable_by_key(|&(key, _)| key);
+        entries.into_iter()
+    }
+    /// like `iter`, but splits each entry's values into the early and late value.
+    ///
+    /// each late value is loaded as its entry is reached, so for tables shared
+    /// between threads the late values aren't a consistent snapshot of the
+    /// whole table.
+    #[allow(clippy::type_complexity)]
+    pub fn iter_full(
+        &self,
+    ) -> impl Iterator<
+        Item = (
+            Key,
+            &<Entry::Values as TableEntryValues>::EarlyValue,
+            Option<<Entry::Values as TableEntryValues>::LateValue>,
+        ),
+    > {
+        self.iter()
+            .map(|(key, values)| (key, values.early_value(), values.late_value()))
+    }
+}
+
+impl<Entry: TableEntry, BH: BuildHasher> HashTable<Entry, BH, LinearProbeSequence> {
+    /// removes `key`'s entry, returning its values.
+    ///
+    /// Rather than leaving a tombstone, the entries after it in the same run
+    /// of full slots are shifted back into the gap, as long as that doesn't
+    /// put them before the slot their hash picks (Knuth's Algorithm R). That
+    /// leaves the table as if `key` had never been inserted, so lookups don't
+    /// slow down after many removes, but it only works with linear probing.
+    ///
+    /// Entries are moved to other slots, so anything derived from slot
+    /// positions must be recomputed afterward; explicit ids move with their
+    /// entries.
+    pub fn remove(&mut self, key: Key) -> Option<Entry::Values> {
+        let (mut hole, _) = self.find_entry(key)?;
+        let table_index_mask = self.capacity() - 1;
+        let (_, values) = self.table.as_mut().expect("table is known to be Some")[hole]
+            .take()
+            .expect("entry is known to be full");
+        if let Some(ids) = &mut self.ids {
+            *ids[hole].get_mut() = 0;
+        }
+        let mut table_index = hole;
+        loop {
+            table_index = (table_index + 1) & table_index_mask;
+            let entry_key =
+                match self.table.as_ref().expect("table is known to be Some")[table_index].get() {
+                    Some((entry_key, _)) => entry_key,
+                    None => break,
+                };
+            let home = self
+                .table_indexes(entry_key, 1)
+                .next()
+                .expect("table is known to be non-empty");
+            // the entry can move back only if the hole isn't before its home slot
+            if table_index.wrapping_sub(home) & table_index_mask
+                < table_index.wrapping_sub(hole) & table_index_mask
+            {
+                continue;
+            }
+            let table = self.table.as_mut().expect("table is known to be Some");
+            let (entry_key, entry_values) = table[table_index]
+                .take()
+                .expect("entry is known to be full");
+            assert!(table[hole].fill(entry_key, entry_values).is_ok());
+            if let Some(ids) = &mut self.ids {
+                *ids[hole].get_mut() = std::mem::take(ids[table_index].get_mut());
+            }
+            hole = table_index;
+        }
+        *self.len.get_mut() -= 1;
+        Some(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::BuildHasherDefault;
+    use std::sync::Arc;
+
+    /// hasher that sends every key to slot 0, for building collision chains
+    #[derive(Default)]
+    struct ConstantHasher;
+
+    impl Hasher for ConstantHasher {
+        fn finish(&self) -> u64 {
+            0
+        }
+        fn write(&mut self, _bytes: &[u8]) {}
+    }
+
+    type ConstantBuildHasher = BuildHasherDefault<ConstantHasher>;
+
+    fn make_key(v: u32) -> Key {
+        let v = NonZeroU32::new(v).unwrap();
+        Key([[[v; 2]; 2]; 2])
+    }
+
+    fn make_values<T: TableEntry>() -> T::Values
+    where
+        T::Values: TableEntryValues<EarlyValue = (), LateValue = NonZeroU32>,
+    {
+        T::Values::new((), None)
+    }
+
+    #[derive(Debug)]
+    struct DropCounter {
+        drop_count: Arc<AtomicUsize>,
+    }
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            let value = self.drop_count.fetch_add(1, Ordering::Relaxed);
+            println!("DropCounter::drop: {}", value);
         }
     }
 
@@ -375,4 +1576,1151 @@ mod tests {
         assert_eq!(drop_count.load(Ordering::Relaxed), 2);
         assert_eq!(drop_count2.load(Ordering::Relaxed), 1);
     }
+
+    #[test]
+    fn test_iter_skips_empty_slots() {
+        type Entry = LocalTableEntry<u32, NonZeroU32>;
+        let table = HashTable::<Entry, rustc_hash::FxBuildHasher>::new(1024);
+        for i in 1..=10 {
+            assert!(table
+                .insert(make_key(i), <Entry as TableEntry>::Values::new(i, None))
+                .is_ok());
+        }
+        let mut found: Vec<_> = table
+            .iter()
+            .map(|(_, values)| *values.early_value())
+            .collect();
+        found.sort_unstable();
+        assert_eq!(found, (1..=10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_adaptive_search_limit() {
+        assert_eq!(adaptive_search_limit(0, 1024), MIN_ADAPTIVE_SEARCH_LIMIT);
+        assert_eq!(adaptive_search_limit(512, 1024), 10);
+        assert_eq!(adaptive_search_limit(768, 1024), 34);
+        assert_eq!(adaptive_search_limit(1020, 1024), 1024);
+        assert_eq!(adaptive_search_limit(1024, 1024), 1024);
+        assert_eq!(adaptive_search_limit(0, 4), 4);
+        // `capacity + free` would overflow here
+        assert_eq!(adaptive_search_limit(1, usize::MAX), 10);
+    }
+
+    #[test]
+    fn test_adaptive_search_limit_near_full() {
+        type Entry = LocalTableEntry<(), NonZeroU32>;
+        let mut table = HashTable::<Entry, ConstantBuildHasher>::with_search_limit(64, usize::MAX);
+        for i in 1..=60 {
+            table
+                .insert(make_key(i), make_values::<Entry>())
+                .ok()
+                .unwrap();
+        }
+        assert_eq!(table.len(), 60);
+        table.set_insert_search_limit(32);
+        match table.insert(make_key(61), make_values::<Entry>()) {
+            Err(InsertFailureReason::TableFullOrSearchLimitHit { .. }) => {}
+            _ => panic!("expected search limit to be hit"),
+        }
+        table.enable_adaptive_search_limit();
+        assert_eq!(table.insert_search_limit(), 64);
+        table
+            .insert(make_key(61), make_values::<Entry>())
+            .ok()
+            .unwrap();
+        assert_eq!(table.len(), 61);
+        assert!(table.find(make_key(61)).is_some());
+        table.set_insert_search_limit(32);
+        assert!(!table.is_adaptive_search_limit_enabled());
+        assert_eq!(table.drain().count(), 61);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_try_with_capacity() {
+        type Table = HashTable<LocalTableEntry<(), NonZeroU32>, ConstantBuildHasher>;
+        assert_eq!(
+            Table::try_with_capacity(usize::MAX, 32, Default::default()).err(),
+            Some(CapacityError::TooBig {
+                capacity: usize::MAX
+            })
+        );
+        // a power of two, but too many bytes
+        let capacity = usize::MAX / 2 + 1;
+        assert_eq!(
+            Table::try_with_capacity(capacity, 32, Default::default()).err(),
+            Some(CapacityError::TooBig { capacity })
+        );
+        let table = Table::try_with_capacity(100, 32, Default::default()).unwrap();
+        assert_eq!(table.capacity(), 128);
+        assert!(table
+            .insert(
+                make_key(1),
+                make_values::<LocalTableEntry<(), NonZeroU32>>()
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_insert_with_limit() {
+        type Entry = LocalTableEntry<(), NonZeroU32>;
+        let table = HashTable::<Entry, ConstantBuildHasher>::with_search_limit(64, usize::MAX);
+        for i in 1..=32 {
+            table
+                .insert(make_key(i), make_values::<Entry>())
+                .ok()
+                .unwrap();
+        }
+        match table.insert_with_limit(make_key(33), make_values::<Entry>(), 4) {
+            Err(InsertFailureReason::TableFullOrSearchLimitHit { .. }) => {}
+            _ => panic!("expected search limit to be hit"),
+        }
+        assert!(table.find(make_key(33)).is_none());
+        assert_eq!(table.insert_search_limit(), usize::MAX);
+        table
+            .insert(make_key(33), make_values::<Entry>())
+            .ok()
+            .unwrap();
+        assert_eq!(table.len(), 33);
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        type Entry = SyncTableEntry<(), NonZeroU32>;
+        let mut table = HashTable::<Entry, ConstantBuildHasher>::new(1024);
+        table.set_insert_search_limit(usize::MAX);
+        for i in 1..=100 {
+            table
+                .insert(make_key(i), make_values::<Entry>())
+                .ok()
+                .unwrap();
+        }
+        assert_eq!(table.shrink_to_fit(), 256);
+        assert_eq!(table.capacity(), 256);
+        assert_eq!(table.len(), 100);
+        for i in 1..=100 {
+            assert!(table.find(make_key(i)).is_some());
+        }
+        assert!(table.find(make_key(101)).is_none());
+        assert_eq!(table.shrink_to_fit(), 256);
+        table.drain().for_each(std::mem::drop);
+        assert_eq!(table.shrink_to_fit(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_inserts() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        const THREAD_COUNT: u32 = 8;
+        const KEYS_PER_THREAD: u32 = 2000;
+        const SHARED_KEYS: u32 = 500;
+        let table =
+            Arc::new(HashTable::<Entry, std::collections::hash_map::RandomState>::new(1 << 16));
+        let threads: Vec<_> = (0..THREAD_COUNT)
+            .map(|thread_index| {
+                let table = table.clone();
+                std::thread::spawn(move || {
+                    // keys 1..=SHARED_KEYS are inserted by every thread
+                    let first_disjoint_key = SHARED_KEYS + 1 + thread_index * KEYS_PER_THREAD;
+                    let keys = (1..=SHARED_KEYS)
+                        .chain(first_disjoint_key..first_disjoint_key + KEYS_PER_THREAD);
+                    for key in keys {
+                        let success = table
+                            .get_or_insert(
+                                make_key(key),
+                                <Entry as TableEntry>::Values::new(key, None),
+                            )
+                            .ok()
+                            .unwrap();
+                        assert_eq!(*success.entry_value.early_value(), key);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let distinct_key_count = SHARED_KEYS + THREAD_COUNT * KEYS_PER_THREAD;
+        assert_eq!(table.len(), distinct_key_count as usize);
+        for key in 1..=distinct_key_count {
+            assert_eq!(*table.find(make_key(key)).unwrap().early_value(), key);
+        }
+        assert_eq!(table.iter().count(), distinct_key_count as usize);
+    }
+
+    #[test]
+    #[cfg(feature = "memmap2")]
+    fn test_file_backed_table() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        type BH = BuildHasherDefault<rustc_hash::FxHasher>;
+        let path = std::env::temp_dir().join(format!(
+            "parallel-hashlife-test-file-backed-table-{}",
+            std::process::id()
+        ));
+        let open = || {
+            std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+                .unwrap()
+        };
+        {
+            let file = open();
+            let table =
+                unsafe { HashTable::<Entry, BH>::with_file(&file, 64, 32, BH::default()) }.unwrap();
+            assert!(table.is_empty());
+            for i in 1..=10 {
+                let values = <Entry as TableEntry>::Values::new(i * 100, NonZeroU32::new(i));
+                assert!(table.insert(make_key(i), values).is_ok());
+            }
+        }
+        {
+            let file = open();
+            let table =
+                unsafe { HashTable::<Entry, BH>::with_file(&file, 64, 32, BH::default()) }.unwrap();
+            assert_eq!(table.len(), 10);
+            for i in 1..=10 {
+                let values = table.find(make_key(i)).unwrap();
+                assert_eq!(*values.early_value(), i * 100);
+                assert_eq!(values.late_value(), NonZeroU32::new(i));
+            }
+            assert!(table.find(make_key(11)).is_none());
+        }
+        {
+            let file = open();
+            let mut table =
+                unsafe { HashTable::<Entry, BH>::with_file(&file, 64, 32, BH::default()) }.unwrap();
+            assert_eq!(table.retain(|_, values| *values.early_value() <= 500), 5);
+            assert_eq!(table.capacity(), 64);
+        }
+        {
+            let file = open();
+            let table =
+                unsafe { HashTable::<Entry, BH>::with_file(&file, 64, 32, BH::default()) }.unwrap();
+            assert_eq!(table.len(), 5);
+            for i in 1..=10 {
+                assert_eq!(table.find(make_key(i)).is_some(), i <= 5);
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "memmap2")]
+    #[should_panic(expected = "can't resize a table stored in a file")]
+    fn test_file_backed_table_resize() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        type BH = BuildHasherDefault<rustc_hash::FxHasher>;
+        let path = std::env::temp_dir().join(format!(
+            "parallel-hashlife-test-file-backed-table-resize-{}",
+            std::process::id()
+        ));
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        // the test panics, so remove the file while it's still open
+        std::fs::remove_file(&path).unwrap();
+        let mut table =
+            unsafe { HashTable::<Entry, BH>::with_file(&file, 64, 32, BH::default()) }.unwrap();
+        table.reserve(100);
+    }
+
+    /// delegates to `Entry`, but without `zeroed_is_empty`, so tables of it
+    /// are built by constructing each entry
+    struct ConstructedEntry<Entry>(Entry);
+
+    impl<Entry: TableEntry> TableEntry for ConstructedEntry<Entry> {
+        type Values = Entry::Values;
+        fn empty() -> Self {
+            ConstructedEntry(Entry::empty())
+        }
+        fn get(&self) -> Option<(Key, &Self::Values)> {
+            self.0.get()
+        }
+        fn fill(
+            &self,
+            key: Key,
+            value: Self::Values,
+        ) -> Result<&Self::Values, AlreadyFull<Self::Values>> {
+            self.0.fill(key, value)
+        }
+        fn take(&mut self) -> Option<(Key, Self::Values)> {
+            self.0.take()
+        }
+    }
+
+    #[test]
+    fn test_zeroed_tables() {
+        test_zeroed_table::<SyncTableEntry<u32, NonZeroU32>>();
+        test_zeroed_table::<LocalTableEntry<u32, NonZeroU32>>();
+    }
+
+    fn test_zeroed_table<Entry: TableEntry>()
+    where
+        Entry::Values: TableEntryValues<EarlyValue = u32, LateValue = NonZeroU32>,
+    {
+        assert!(Entry::zeroed_is_empty().is_some());
+        assert!(ConstructedEntry::<Entry>::zeroed_is_empty().is_none());
+        let mut zeroed = HashTable::<Entry, ConstantBuildHasher>::new(64);
+        let mut constructed = HashTable::<ConstructedEntry<Entry>, ConstantBuildHasher>::new(64);
+        assert_eq!(zeroed.capacity(), constructed.capacity());
+        assert_eq!(zeroed.iter().count(), 0);
+        for i in 1..=20 {
+            let late_value = NonZeroU32::new(i % 3);
+            assert!(zeroed
+                .insert(make_key(i), Entry::Values::new(i, late_value))
+                .is_ok());
+            assert!(constructed
+                .insert(make_key(i), Entry::Values::new(i, late_value))
+                .is_ok());
+        }
+        for i in 1..=40 {
+            let zeroed_values = zeroed.find(make_key(i));
+            let constructed_values = constructed.find(make_key(i));
+            assert_eq!(
+                zeroed_values.map(|v| (*v.early_value(), v.late_value())),
+                constructed_values.map(|v| (*v.early_value(), v.late_value()))
+            );
+        }
+        let zeroed_keys: Vec<_> = zeroed.iter().map(|(key, _)| key).collect();
+        let constructed_keys: Vec<_> = constructed.iter().map(|(key, _)| key).collect();
+        assert_eq!(zeroed_keys, constructed_keys);
+        assert_eq!(zeroed.drain().count(), 20);
+        assert_eq!(constructed.drain().count(), 20);
+    }
+
+    #[test]
+    fn test_clear() {
+        type Entry = LocalTableEntry<DropCounter, NonZeroU32>;
+        let drop_count = Arc::new(AtomicUsize::new(0));
+        let mut table = HashTable::<Entry, std::collections::hash_map::RandomState>::new(32);
+        let insert_all = |table: &HashTable<Entry, _>| {
+            for i in 1..=10 {
+                let values = <Entry as TableEntry>::Values::new(
+                    DropCounter {
+                        drop_count: drop_count.clone(),
+                    },
+                    None,
+                );
+                assert!(table.insert(make_key(i), values).is_ok());
+            }
+        };
+        insert_all(&table);
+        table.clear();
+        assert_eq!(drop_count.load(Ordering::Relaxed), 10);
+        assert!(table.is_empty());
+        assert_eq!(table.capacity(), 32);
+        assert!(table.find(make_key(1)).is_none());
+        insert_all(&table);
+        assert_eq!(table.len(), 10);
+        assert!(table.find(make_key(1)).is_some());
+        std::mem::drop(table);
+        assert_eq!(drop_count.load(Ordering::Relaxed), 20);
+    }
+
+    #[test]
+    fn test_entry() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        let table = HashTable::<Entry, rustc_hash::FxBuildHasher>::new(16);
+        let new_values = |v| <Entry as TableEntry>::Values::new(v, None);
+        match table.entry(make_key(1)) {
+            HashTableEntry::Vacant(vacant) => {
+                assert_eq!(vacant.key(), make_key(1));
+                let values = vacant.insert(new_values(1)).ok().unwrap();
+                assert_eq!(*values.early_value(), 1);
+            }
+            HashTableEntry::Occupied(_) => panic!("expected vacant entry"),
+        }
+        match table.entry(make_key(1)) {
+            HashTableEntry::Occupied(values) => assert_eq!(*values.early_value(), 1),
+            HashTableEntry::Vacant(_) => panic!("expected occupied entry"),
+        }
+
+        // both lookups miss, then both insert: the second gets the first's values
+        let (first, second) = match (table.entry(make_key(2)), table.entry(make_key(2))) {
+            (HashTableEntry::Vacant(first), HashTableEntry::Vacant(second)) => (first, second),
+            _ => panic!("expected vacant entries"),
+        };
+        assert_eq!(*first.insert(new_values(2)).ok().unwrap().early_value(), 2);
+        assert_eq!(*second.insert(new_values(3)).ok().unwrap().early_value(), 2);
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn test_iter_sorted() {
+        type Entry = LocalTableEntry<u32, NonZeroU32>;
+        let new_table = || {
+            let table = HashTable::<Entry, std::collections::hash_map::RandomState>::new(64);
+            for i in (1..=40).rev() {
+                let values = <Entry as TableEntry>::Values::new(i, None);
+                assert!(table.insert(make_key(i), values).is_ok());
+            }
+            table
+        };
+        let sorted = |table: &HashTable<Entry, _>| -> Vec<(Key, u32)> {
+            table
+                .iter_sorted()
+                .map(|(key, values)| (key, *values.early_value()))
+                .collect()
+        };
+        let table1 = new_table();
+        let table2 = new_table();
+        assert_eq!(sorted(&table1), sorted(&table2));
+        assert_eq!(
+            sorted(&table1),
+            (1..=40).map(|i| (make_key(i), i)).collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_stats() {
+        type Entry = SyncTableEntry<(), NonZeroU32>;
+        let table = CachedHashTable::new(
+            HashTable::<Entry, ConstantBuildHasher>::with_search_limit(4, 4),
+        );
+        for i in 1..=5 {
+            let _ = table.table().insert(make_key(i), make_values::<Entry>());
+        }
+        // already in the table, so neither an insert nor a search limit hit
+        let _ = table.table().insert(make_key(1), make_values::<Entry>());
+        assert!(table.table().find(make_key(1)).is_some());
+        assert!(table.table().find(make_key(5)).is_none());
+        assert!(table.table().find_quiet(make_key(5)).is_none());
+        assert!(table.table().contains_key(make_key(2)));
+        assert!(table.find(make_key(2)).is_some());
+        assert!(table.find(make_key(2)).is_some());
+        assert_eq!(
+            table.table().stats_snapshot(),
+            HashTableStats {
+                inserts: 4,
+                finds: 4,
+                find_misses: 1,
+                search_limit_hits: 1,
+                cache_hits: 1,
+            }
+        );
+        table.table().reset_stats();
+        assert_eq!(table.table().stats_snapshot(), HashTableStats::default());
+    }
+
+    #[test]
+    fn test_find_unsynchronized() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        let table = Arc::new(HashTable::<Entry, ConstantBuildHasher>::with_search_limit(
+            64,
+            usize::MAX,
+        ));
+        // fill from another thread so the reads below depend on the join for
+        // their ordering, the way a read-only phase after a parallel step would
+        let filler = {
+            let table = table.clone();
+            std::thread::spawn(move || {
+                for i in 1..=40 {
+                    let values = <Entry as TableEntry>::Values::new(i, None);
+                    assert!(table.insert(make_key(i), values).is_ok());
+                }
+            })
+        };
+        filler.join().unwrap();
+        // every key collides, so finding key `i` walks past `i - 1` other
+        // full entries, all read with `get_unsynchronized`
+        for i in 1..=40 {
+            // safety: the only thread that inserted was joined above, and
+            // nothing inserts while we read
+            let values = unsafe { table.find_unsynchronized(make_key(i)) };
+            assert_eq!(values.map(|values| *values.early_value()), Some(i));
+        }
+        // a miss walks every full entry and stops at the first empty one
+        // safety: as above
+        assert!(unsafe { table.find_unsynchronized(make_key(41)) }.is_none());
+    }
+
+    #[test]
+    fn test_remove() {
+        type Entry = LocalTableEntry<u32, NonZeroU32>;
+        type Table = HashTable<Entry, rustc_hash::FxBuildHasher>;
+        let mut state = 0x1234_5678u32;
+        let keys: Vec<Key> = (0..3000)
+            .map(|_| {
+                // xorshift32
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                Key([[[NonZeroU32::new(state).unwrap(); 2]; 2]; 2])
+            })
+            .collect();
+        let capacity = 1 << 10;
+        let mut table = Table::with_search_limit(capacity, usize::MAX);
+        table.enable_explicit_ids();
+        let mut present = std::collections::HashMap::new();
+        // keep the table about 90% full while cycling through all the keys
+        for (i, &key) in keys.iter().enumerate() {
+            let id = table
+                .intern(key, <Entry as TableEntry>::Values::new(i as u32, None))
+                .ok()
+                .unwrap();
+            present.insert(key, (i as u32, id));
+            if i >= capacity * 9 / 10 {
+                let old_key = keys[i - capacity * 9 / 10];
+                let values = table.remove(old_key).unwrap();
+                assert_eq!(*values.early_value(), present.remove(&old_key).unwrap().0);
+                assert!(table.remove(old_key).is_none());
+            }
+        }
+        assert_eq!(table.len(), present.len());
+        for (&key, &(value, id)) in &present {
+            assert_eq!(
+                table.find(key).map(|values| *values.early_value()),
+                Some(value)
+            );
+            assert_eq!(table.id_of(key), Some(id));
+        }
+        assert_eq!(table.explicit_ids().count(), present.len());
+        // with linear probing, the total probe length doesn't depend on the
+        // order keys were inserted in, so without tombstones it must match a
+        // table that only ever had the present keys inserted
+        let fresh = Table::with_search_limit(capacity, usize::MAX);
+        for &key in present.keys() {
+            assert!(fresh
+                .insert(key, <Entry as TableEntry>::Values::new(0, None))
+                .is_ok());
+        }
+        let stats = table.probe_length_stats();
+        assert!((stats.mean - fresh.probe_length_stats().mean).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fill_ratio_histogram() {
+        type Entry = LocalTableEntry<(), NonZeroU32>;
+        let table = HashTable::<Entry, ConstantBuildHasher>::with_search_limit(16, usize::MAX);
+        assert_eq!(table.fill_ratio_histogram(), Vec::<usize>::new());
+        // every key hashes to the same slot, so the nth key inserted is n
+        // slots past it
+        for i in 1..=5 {
+            assert!(table.insert(make_key(i), make_values::<Entry>()).is_ok());
+        }
+        assert_eq!(table.fill_ratio_histogram(), vec![1, 1, 1, 1, 1]);
+        let stats = table.probe_length_stats();
+        assert_eq!(stats.max, 5);
+    }
+
+    #[test]
+    fn test_into_drained() {
+        type Entry = SyncTableEntry<DropCounter, NonZeroU32>;
+        let drop_count = Arc::new(AtomicUsize::new(0));
+        let table = Arc::new(HashTable::<Entry, std::collections::hash_map::RandomState>::new(32));
+        let threads: Vec<_> = (0..2)
+            .map(|thread_index| {
+                let table = table.clone();
+                let drop_count = drop_count.clone();
+                std::thread::spawn(move || {
+                    for i in 1..=5 {
+                        let values = <Entry as TableEntry>::Values::new(
+                            DropCounter {
+                                drop_count: drop_count.clone(),
+                            },
+                            None,
+                        );
+                        assert!(table.insert(make_key(thread_index * 5 + i), values).is_ok());
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let table = Arc::try_unwrap(table)
+            .ok()
+            .expect("all threads were joined");
+        assert_eq!(table.len(), 10);
+        let mut drain = table.into_drained();
+        let mut drained_keys: Vec<Key> = drain.by_ref().take(4).map(|(key, _)| key).collect();
+        assert_eq!(drop_count.load(Ordering::Relaxed), 4);
+        drained_keys.extend(drain.by_ref().map(|(key, _)| key));
+        assert_eq!(drop_count.load(Ordering::Relaxed), 10);
+        assert!(drain.next().is_none());
+        std::mem::drop(drain);
+        assert_eq!(drop_count.load(Ordering::Relaxed), 10);
+        drained_keys.sort_by_key(|key| key.0[0][0][0]);
+        assert_eq!(drained_keys, (1..=10).map(make_key).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_into_drained_drops_rest() {
+        type Entry = LocalTableEntry<DropCounter, NonZeroU32>;
+        let drop_count = Arc::new(AtomicUsize::new(0));
+        let table = HashTable::<Entry, std::collections::hash_map::RandomState>::new(32);
+        for i in 1..=10 {
+            let values = <Entry as TableEntry>::Values::new(
+                DropCounter {
+                    drop_count: drop_count.clone(),
+                },
+                None,
+            );
+            assert!(table.insert(make_key(i), values).is_ok());
+        }
+        let mut drain = table.into_drained();
+        assert!(drain.next().is_some());
+        assert_eq!(drop_count.load(Ordering::Relaxed), 1);
+        std::mem::drop(drain);
+        assert_eq!(drop_count.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn test_insert_or_grow() {
+        type Entry = LocalTableEntry<(), NonZeroU32>;
+        // no search limit, so the table only grows because of the load factor
+        let mut table =
+            HashTable::<Entry, std::collections::hash_map::RandomState>::with_search_limit(
+                4,
+                usize::MAX,
+            );
+        let mut capacities = vec![table.capacity()];
+        for i in 1..=1000 {
+            let len_before = table.len();
+            let capacity_before = table.capacity();
+            assert!(table
+                .insert_or_grow(make_key(i), make_values::<Entry>())
+                .is_ok());
+            assert!(table.len() as f64 <= table.capacity() as f64 * 0.75);
+            if table.capacity() != capacity_before {
+                // growth only happens when needed to stay under the max load factor
+                assert!((len_before + 1) as f64 > capacity_before as f64 * 0.75);
+                capacities.push(table.capacity());
+            }
+        }
+        assert_eq!(
+            capacities,
+            vec![4, 8, 16, 32, 64, 128, 256, 512, 1024, 2048]
+        );
+        for i in 1..=1000 {
+            assert!(table.find(make_key(i)).is_some());
+        }
+        match table.insert_or_grow(make_key(1), make_values::<Entry>()) {
+            Err(InsertFailureReason::AlreadyInTable { .. }) => {}
+            _ => panic!("expected key to already be in table"),
+        }
+        table.set_growth_policy(GrowthPolicy {
+            max_load_factor: 0.5,
+            growth_factor: 4,
+        });
+        table.reserve(24);
+        assert_eq!(table.capacity(), 2048);
+        table.reserve(25);
+        assert_eq!(table.capacity(), 8192);
+        assert_eq!(table.len(), 1000);
+    }
+
+    #[test]
+    fn test_find_quiet() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        let table = HashTable::<Entry, ConstantBuildHasher>::new(16);
+        for i in 1..=5 {
+            assert!(table
+                .insert(make_key(i), <Entry as TableEntry>::Values::new(i, None))
+                .is_ok());
+        }
+        for i in 1..=8 {
+            assert_eq!(
+                table.find_quiet(make_key(i)).map(|v| *v.early_value()),
+                table.find(make_key(i)).map(|v| *v.early_value())
+            );
+        }
+        assert!(table.find_quiet(make_key(6)).is_none());
+    }
+
+    #[test]
+    fn test_iter_full() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        let table = HashTable::<Entry, std::collections::hash_map::RandomState>::new(64);
+        for i in 1..=20 {
+            let late_value = if i % 3 == 0 {
+                NonZeroU32::new(i * 100)
+            } else {
+                None
+            };
+            assert!(table
+                .insert(
+                    make_key(i),
+                    <Entry as TableEntry>::Values::new(i, late_value)
+                )
+                .is_ok());
+        }
+        table
+            .find(make_key(1))
+            .unwrap()
+            .set_late_value(NonZeroU32::new(7));
+        let mut entries: Vec<_> = table
+            .iter_full()
+            .map(|(key, &early_value, late_value)| (key, early_value, late_value))
+            .collect();
+        entries.sort_by_key(|&(_, early_value, _)| early_value);
+        let expected: Vec<_> = (1..=20)
+            .map(|i| {
+                let late_value = match i {
+                    1 => NonZeroU32::new(7),
+                    _ if i % 3 == 0 => NonZeroU32::new(i * 100),
+                    _ => None,
+                };
+                (make_key(i), i, late_value)
+            })
+            .collect();
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn test_insert_or_grow_clustered() {
+        type Entry = LocalTableEntry<(), NonZeroU32>;
+        // every key collides, so growing never makes room past the search limit
+        let mut table = HashTable::<Entry, ConstantBuildHasher>::with_search_limit(64, 4);
+        for i in 1..=4 {
+            assert!(table
+                .insert_or_grow(make_key(i), make_values::<Entry>())
+                .is_ok());
+        }
+        assert_eq!(table.capacity(), 64);
+        match table.insert_or_grow(make_key(5), make_values::<Entry>()) {
+            Err(InsertFailureReason::TableFullOrSearchLimitHit { .. }) => {}
+            _ => panic!("expected search limit to be hit"),
+        }
+        assert_eq!(table.capacity(), 128);
+        assert_eq!(table.len(), 4);
+        table.set_insert_search_limit(0);
+        match table.insert_or_grow(make_key(5), make_values::<Entry>()) {
+            Err(InsertFailureReason::TableFullOrSearchLimitHit { .. }) => {}
+            _ => panic!("expected search limit to be hit"),
+        }
+        assert_eq!(table.capacity(), 256);
+    }
+
+    #[test]
+    fn test_insert_or_replace() {
+        type Entry = LocalTableEntry<DropCounter, NonZeroU32>;
+        let drop_count = Arc::new(AtomicUsize::new(0));
+        let new_values = |late_value| {
+            <Entry as TableEntry>::Values::new(
+                DropCounter {
+                    drop_count: drop_count.clone(),
+                },
+                NonZeroU32::new(late_value),
+            )
+        };
+        // every key collides, so each key's slot depends on insertion order
+        let mut table = HashTable::<Entry, ConstantBuildHasher>::new(8);
+        for i in 1..=3 {
+            assert!(table
+                .insert_or_replace(make_key(i), new_values(i))
+                .ok()
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(table.len(), 3);
+        let slot = |table: &HashTable<Entry, _>| &*table.find(make_key(2)).unwrap() as *const _;
+        let slot_before = slot(&table);
+        let old_values = table
+            .insert_or_replace(make_key(2), new_values(20))
+            .ok()
+            .unwrap()
+            .unwrap();
+        assert_eq!(slot(&table), slot_before);
+        assert_eq!(table.len(), 3);
+        assert_eq!(old_values.late_value(), NonZeroU32::new(2));
+        assert_eq!(
+            table.find(make_key(2)).unwrap().late_value(),
+            NonZeroU32::new(20)
+        );
+        assert_eq!(drop_count.load(Ordering::Relaxed), 0);
+        std::mem::drop(old_values);
+        assert_eq!(drop_count.load(Ordering::Relaxed), 1);
+        std::mem::drop(table);
+        assert_eq!(drop_count.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_capacity_for() {
+        type Table = HashTable<LocalTableEntry<(), NonZeroU32>, ConstantBuildHasher>;
+        assert_eq!(Table::capacity_for(0, 0.75), 1);
+        assert_eq!(Table::capacity_for(0, 1.0), 1);
+        assert_eq!(Table::capacity_for(1, 1.0), 1);
+        assert_eq!(Table::capacity_for(1, 0.5), 2);
+        assert_eq!(Table::capacity_for(8, 1.0), 8);
+        assert_eq!(Table::capacity_for(9, 1.0), 16);
+        assert_eq!(Table::capacity_for(3, 0.75), 4);
+        assert_eq!(Table::capacity_for(6, 0.75), 8);
+        assert_eq!(Table::capacity_for(7, 0.75), 16);
+        assert_eq!(Table::capacity_for(1 << 20, 1.0), 1 << 20);
+        assert_eq!(Table::capacity_for((1 << 20) + 1, 1.0), 1 << 21);
+        for expected_entries in 0..100 {
+            let capacity = Table::capacity_for(expected_entries, 0.75);
+            assert!(GrowthPolicy::default().max_len(capacity) >= expected_entries);
+            assert!(
+                capacity == 1 || GrowthPolicy::default().max_len(capacity / 2) < expected_entries
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_peek_key00() {
+        test_find_peek_key00_for::<LocalTableEntry<u32, NonZeroU32>>();
+        test_find_peek_key00_for::<SyncTableEntry<u32, NonZeroU32>>();
+    }
+
+    fn test_find_peek_key00_for<Entry: TableEntry>()
+    where
+        Entry::Values: TableEntryValues<EarlyValue = u32, LateValue = NonZeroU32>,
+    {
+        let id = |v| NonZeroU32::new(v).unwrap();
+        // keys that share `key.0[0][0]` only differ after the early reject
+        let key = |key00, rest| {
+            Key([
+                [[id(key00), id(key00)], [id(rest), id(rest)]],
+                [[id(rest); 2]; 2],
+            ])
+        };
+        let table = HashTable::<Entry, ConstantBuildHasher>::new(64);
+        let mut inserted = Vec::new();
+        for key00 in 1..=4 {
+            for rest in 1..=4 {
+                let v = key00 * 10 + rest;
+                assert!(table
+                    .insert(key(key00, rest), Entry::Values::new(v, None))
+                    .is_ok());
+                inserted.push((key(key00, rest), v));
+            }
+        }
+        for &(key, v) in &inserted {
+            assert_eq!(table.find(key).map(|values| *values.early_value()), Some(v));
+        }
+        for entry in table.get_table() {
+            if let Some(key00) = entry.peek_key00() {
+                assert_eq!(entry.get().map(|(key, _)| key.0[0][0]), Some(key00));
+            }
+        }
+        for key00 in 1..=5 {
+            assert!(table.find(key(key00, 5)).is_none());
+        }
+        assert!(table.find(key(5, 1)).is_none());
+    }
+
+    #[test]
+    fn test_linear_probe_sequence() {
+        let slots: Vec<_> = LinearProbeSequence::new(0x1234_5678_9ABC_DEF5, 7)
+            .take(10)
+            .collect();
+        assert_eq!(slots, vec![5, 6, 7, 0, 1, 2, 3, 4, 5, 6]);
+        let slots: Vec<_> = LinearProbeSequence::new(3, 0).take(3).collect();
+        assert_eq!(slots, vec![0, 0, 0]);
+        for hash in 0..40 {
+            let mut slots: Vec<_> = LinearProbeSequence::new(hash, 15).take(16).collect();
+            assert_eq!(slots[0], hash as usize % 16);
+            slots.sort_unstable();
+            assert_eq!(slots, (0..16).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_custom_probe_sequence() {
+        /// probes every other slot first, to check tables use `P`
+        struct EvenFirst {
+            step: usize,
+            table_index_mask: usize,
+        }
+
+        impl ProbeSequence for EvenFirst {
+            fn new(_hash: u64, table_index_mask: usize) -> Self {
+                Self {
+                    step: 0,
+                    table_index_mask,
+                }
+            }
+        }
+
+        impl Iterator for EvenFirst {
+            type Item = usize;
+            fn next(&mut self) -> Option<usize> {
+                let capacity = self.table_index_mask + 1;
+                let half = capacity / 2;
+                let step = self.step % capacity;
+                self.step += 1;
+                Some(if step < half {
+                    step * 2
+                } else {
+                    (step - half) * 2 + 1
+                })
+            }
+        }
+
+        type Entry = LocalTableEntry<u32, NonZeroU32>;
+        let table = HashTable::<Entry, ConstantBuildHasher, EvenFirst>::new(8);
+        for i in 1..=8 {
+            assert!(table
+                .insert(make_key(i), <Entry as TableEntry>::Values::new(i, None))
+                .is_ok());
+        }
+        let early_values: Vec<_> = table
+            .iter()
+            .map(|(_, values)| *values.early_value())
+            .collect();
+        assert_eq!(early_values, vec![1, 5, 2, 6, 3, 7, 4, 8]);
+        for i in 1..=8 {
+            assert_eq!(
+                table.find(make_key(i)).map(|values| *values.early_value()),
+                Some(i)
+            );
+        }
+    }
+
+    #[test]
+    fn test_explicit_ids() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        let mut table =
+            HashTable::<Entry, rustc_hash::FxBuildHasher>::with_search_limit(4, usize::MAX);
+        assert!(table
+            .insert(make_key(1), <Entry as TableEntry>::Values::new(1, None))
+            .is_ok());
+        let success = table
+            .get_or_insert(make_key(1), <Entry as TableEntry>::Values::new(0, None))
+            .ok()
+            .unwrap();
+        assert_eq!(success.id(), None);
+        table.enable_explicit_ids();
+        assert_eq!(table.id_of(make_key(1)), NonZeroU32::new(1));
+        for i in 2..=4 {
+            let id = table
+                .intern(make_key(i), <Entry as TableEntry>::Values::new(i, None))
+                .ok()
+                .unwrap();
+            assert_eq!(id.get(), i);
+        }
+        // interning a key that's already present gives its existing id
+        let id = table
+            .intern(make_key(3), <Entry as TableEntry>::Values::new(0, None))
+            .ok()
+            .unwrap();
+        assert_eq!(id.get(), 3);
+        assert_eq!(table.id_of(make_key(5)), None);
+        for _ in 0..2 {
+            let success = table
+                .get_or_insert(make_key(2), <Entry as TableEntry>::Values::new(0, None))
+                .ok()
+                .unwrap();
+            assert_eq!(success.id(), NonZeroU32::new(2));
+            assert_eq!(*success.entry_value().early_value(), 2);
+        }
+        match table.insert(make_key(4), <Entry as TableEntry>::Values::new(0, None)) {
+            Err(InsertFailureReason::AlreadyInTable { id, .. }) => {
+                assert_eq!(id, NonZeroU32::new(4))
+            }
+            _ => panic!("expected key to already be in table"),
+        }
+        let slots_before: Vec<_> = table
+            .iter()
+            .map(|(_, values)| *values.early_value())
+            .collect();
+        table.reserve(100);
+        let slots_after: Vec<_> = table
+            .iter()
+            .map(|(_, values)| *values.early_value())
+            .collect();
+        assert_ne!(slots_before, slots_after, "entries should have moved");
+        for i in 1..=4 {
+            assert_eq!(table.id_of(make_key(i)), NonZeroU32::new(i));
+        }
+        assert!(table
+            .insert_or_grow(make_key(5), <Entry as TableEntry>::Values::new(5, None))
+            .is_ok());
+        assert_eq!(table.id_of(make_key(5)), NonZeroU32::new(5));
+        table.clear();
+        assert_eq!(table.id_of(make_key(1)), None);
+        let id = table
+            .intern(make_key(1), <Entry as TableEntry>::Values::new(1, None))
+            .ok()
+            .unwrap();
+        assert_eq!(id.get(), 6, "ids are never reused");
+    }
+
+    #[test]
+    fn test_contains_key() {
+        test_contains_key_for::<LocalTableEntry<(), NonZeroU32>>();
+        test_contains_key_for::<SyncTableEntry<(), NonZeroU32>>();
+    }
+
+    fn test_contains_key_for<Entry: TableEntry>()
+    where
+        Entry::Values: TableEntryValues<EarlyValue = (), LateValue = NonZeroU32>,
+    {
+        // every key collides, so lookups walk the whole chain
+        let table = HashTable::<Entry, ConstantBuildHasher>::new(16);
+        for i in (1..=10).step_by(2) {
+            assert!(table.insert(make_key(i), make_values::<Entry>()).is_ok());
+        }
+        for i in 1..=12 {
+            assert_eq!(
+                table.contains_key(make_key(i)),
+                table.find(make_key(i)).is_some()
+            );
+            assert_eq!(table.contains_key(make_key(i)), i % 2 == 1 && i < 10);
+        }
+    }
+
+    #[test]
+    fn test_probe_report() {
+        type Entry = LocalTableEntry<(), NonZeroU32>;
+        // every key starts probing at slot 0, building one long chain
+        let mut table = HashTable::<Entry, ConstantBuildHasher>::with_search_limit(8, 3);
+        for i in 1..=3 {
+            assert!(table.insert(make_key(i), make_values::<Entry>()).is_ok());
+        }
+        let occupied = |table_index, key, id| Probe {
+            table_index,
+            slot: ProbedSlot::Occupied {
+                key: make_key(key),
+                id: NonZeroU32::new(id),
+            },
+        };
+        match table.insert(make_key(4), make_values::<Entry>()) {
+            Err(InsertFailureReason::TableFullOrSearchLimitHit { .. }) => {}
+            _ => panic!("expected the search limit to be hit"),
+        }
+        assert_eq!(
+            table.probe_report(make_key(4)),
+            ProbeReport {
+                search_limit: 3,
+                probes: vec![occupied(0, 1, 0), occupied(1, 2, 0), occupied(2, 3, 0)],
+            }
+        );
+        assert_eq!(
+            table.probe_report(make_key(2)).probes,
+            vec![occupied(0, 1, 0), occupied(1, 2, 0)]
+        );
+        table.enable_explicit_ids();
+        table.set_insert_search_limit(8);
+        assert_eq!(
+            table.probe_report(make_key(4)),
+            ProbeReport {
+                search_limit: 8,
+                probes: vec![
+                    occupied(0, 1, 1),
+                    occupied(1, 2, 2),
+                    occupied(2, 3, 3),
+                    Probe {
+                        table_index: 3,
+                        slot: ProbedSlot::Empty,
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_with_capacity_bytes() {
+        type Entry = SyncTableEntry<(), NonZeroU32>;
+        let entry_size = std::mem::size_of::<Entry>();
+        for &bytes in &[
+            entry_size,
+            entry_size * 2 - 1,
+            entry_size * 2,
+            entry_size * 1000,
+            1 << 20,
+            (1 << 20) - 1,
+        ] {
+            let table = HashTable::<Entry, ConstantBuildHasher>::with_capacity_bytes(
+                bytes,
+                Default::default(),
+            )
+            .unwrap();
+            assert!(table.capacity() * entry_size <= bytes);
+            assert!(table.capacity() * 2 * entry_size > bytes);
+        }
+    }
+
+    #[test]
+    fn test_with_capacity_bytes_too_small() {
+        type Entry = SyncTableEntry<(), NonZeroU32>;
+        let bytes = std::mem::size_of::<Entry>() - 1;
+        let result =
+            HashTable::<Entry, ConstantBuildHasher>::with_capacity_bytes(bytes, Default::default());
+        assert_eq!(result.err(), Some(CapacityError::TooSmall { bytes }));
+    }
+
+    /// a zero-sized entry that's always empty
+    struct ZeroSizedEntry;
+
+    impl TableEntry for ZeroSizedEntry {
+        type Values = <LocalTableEntry<(), NonZeroU32> as TableEntry>::Values;
+        fn empty() -> Self {
+            ZeroSizedEntry
+        }
+        fn get(&self) -> Option<(Key, &Self::Values)> {
+            None
+        }
+        fn fill(
+            &self,
+            _key: Key,
+            _value: Self::Values,
+        ) -> Result<&Self::Values, AlreadyFull<Self::Values>> {
+            unimplemented!("a zero-sized entry can't hold a key")
+        }
+        fn take(&mut self) -> Option<(Key, Self::Values)> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_with_capacity_bytes_zero_sized_entry() {
+        assert_eq!(std::mem::size_of::<ZeroSizedEntry>(), 0);
+        for &bytes in &[0, 1, 1 << 20] {
+            let result = HashTable::<ZeroSizedEntry, ConstantBuildHasher>::with_capacity_bytes(
+                bytes,
+                Default::default(),
+            );
+            assert_eq!(
+                result.err(),
+                Some(CapacityError::TooBig {
+                    capacity: usize::MAX
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_quadratic_probe_sequence() {
+        let slots: Vec<_> = QuadraticProbeSequence::new(5, 15).take(6).collect();
+        assert_eq!(slots, vec![5, 6, 8, 11, 15, 4]);
+        for log2_capacity in 0..12 {
+            let capacity = 1usize << log2_capacity;
+            for &hash in &[0, 1, 7, 0x1234_5678_9ABC_DEF0] {
+                let mut slots: Vec<_> = QuadraticProbeSequence::new(hash, capacity - 1)
+                    .take(capacity)
+                    .collect();
+                slots.sort_unstable();
+                assert_eq!(slots, (0..capacity).collect::<Vec<_>>());
+            }
+        }
+        // a table can be filled completely and every key is still found
+        type Entry = LocalTableEntry<u32, NonZeroU32>;
+        let table =
+            HashTable::<Entry, ConstantBuildHasher, QuadraticProbeSequence>::with_search_limit(
+                16,
+                usize::MAX,
+            );
+        for i in 1..=16 {
+            assert!(table
+                .insert(make_key(i), <Entry as TableEntry>::Values::new(i, None))
+                .is_ok());
+        }
+        for i in 1..=16 {
+            assert_eq!(
+                table.find(make_key(i)).map(|values| *values.early_value()),
+                Some(i)
+            );
+        }
+        assert!(table.find(make_key(17)).is_none());
+    }
 }
@@ -0,0 +1,193 @@
+use crate::hashtable_base::GetOrInsertFailureReason;
+use crate::hashtable_base::GetOrInsertSuccess;
+use crate::hashtable_base::HashTable;
+use crate::hashtable_base::InsertFailureReason;
+use crate::hashtable_base::Key;
+use crate::hashtable_base::LinearProbeSequence;
+use crate::hashtable_base::ProbeSequence;
+use crate::hashtable_base::Ref;
+use crate::hashtable_base::TableEntry;
+use std::cell::Cell;
+use std::hash::BuildHasher;
+
+const CACHE_SIZE: usize = 4;
+
+/// a `HashTable` with a small direct-mapped cache of recent `find` results in
+/// front of it, for tables where a few keys (like the root) are looked up
+/// constantly.
+///
+/// The cache uses `Cell`s, so this is only for single-threaded use.
+pub struct CachedHashTable<
+    Entry: TableEntry,
+    BH: BuildHasher,
+    P: ProbeSequence = LinearProbeSequence,
+> {
+    table: HashTable<Entry, BH, P>,
+    cache: [CacheEntry<Entry::Values>; CACHE_SIZE],
+    hit_count: Cell<u64>,
+    miss_count: Cell<u64>,
+}
+
+type CacheEntry<Values> = Cell<Option<(Key, *const Values)>>;
+
+/// cheap multiplicative hash of all of `key`'s ids, independent of the table's hasher
+fn cache_index(key: Key) -> usize {
+    let hash = key
+        .0
+        .iter()
+        .flatten()
+        .flatten()
+        .fold(0u32, |hash, id| (hash ^ id.get()).wrapping_mul(0x9E37_79B9));
+    ((u64::from(hash) * CACHE_SIZE as u64) >> 32) as usize
+}
+
+impl<Entry: TableEntry, BH: BuildHasher, P: ProbeSequence> CachedHashTable<Entry, BH, P> {
+    pub fn new(table: HashTable<Entry, BH, P>) -> Self {
+        Self {
+            table,
+            cache: Default::default(),
+            hit_count: Cell::new(0),
+            miss_count: Cell::new(0),
+        }
+    }
+    pub fn table(&self) -> &HashTable<Entry, BH, P> {
+        &self.table
+    }
+    /// invalidates the cache, since the table may be modified
+    pub fn table_mut(&mut self) -> &mut HashTable<Entry, BH, P> {
+        self.invalidate_cache();
+        &mut self.table
+    }
+    pub fn into_inner(self) -> HashTable<Entry, BH, P> {
+        self.table
+    }
+    pub fn invalidate_cache(&self) {
+        for cache_entry in &self.cache {
+            cache_entry.set(None);
+        }
+    }
+    pub fn hit_count(&self) -> u64 {
+        self.hit_count.get()
+    }
+    pub fn miss_count(&self) -> u64 {
+        self.miss_count.get()
+    }
+    pub fn reset_hit_counts(&self) {
+        self.hit_count.set(0);
+        self.miss_count.set(0);
+    }
+    /// only found keys are cached, since missing keys may be inserted later
+    pub fn find(&self, key: Key) -> Option<Ref<Entry::Values>> {
+        let cache_entry = &self.cache[cache_index(key)];
+        if let Some((cached_key, value)) = cache_entry.get() {
+            if cached_key == key {
+                self.hit_count.set(self.hit_count.get() + 1);
+                count_stat!(self.table, finds);
+                count_stat!(self.table, cache_hits);
+                // safety: entries can only be moved or removed through
+                // `table_mut`, which empties the cache first
+                return Some(Ref::new(unsafe { &*value }));
+            }
+        }
+        self.miss_count.set(self.miss_count.get() + 1);
+        let value = self.table.find(key)?;
+        cache_entry.set(Some((key, &*value)));
+        Some(value)
+    }
+    pub fn insert(
+        &self,
+        key: Key,
+        value: Entry::Values,
+    ) -> Result<&Entry::Values, InsertFailureReason<Entry::Values>> {
+        self.table.insert(key, value)
+    }
+    pub fn get_or_insert(
+        &self,
+        key: Key,
+        value: Entry::Values,
+    ) -> Result<GetOrInsertSuccess<Entry::Values>, GetOrInsertFailureReason<Entry::Values>> {
+        self.table.get_or_insert(key, value)
+    }
+}
+
+impl<Entry: TableEntry, BH: BuildHasher, P: ProbeSequence> From<HashTable<Entry, BH, P>>
+    for CachedHashTable<Entry, BH, P>
+{
+    fn from(table: HashTable<Entry, BH, P>) -> Self {
+        Self::new(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashtable_base::LocalTableEntry;
+    use crate::hashtable_base::QuadraticProbeSequence;
+    use crate::hashtable_base::TableEntryValues;
+    use std::collections::hash_map::RandomState;
+    use std::num::NonZeroU32;
+
+    fn make_key(v: u32) -> Key {
+        let id = |i: u32| NonZeroU32::new(v * 8 + i + 1).unwrap();
+        Key([
+            [[id(0), id(1)], [id(2), id(3)]],
+            [[id(4), id(5)], [id(6), id(7)]],
+        ])
+    }
+
+    #[test]
+    fn test_cached_hash_table() {
+        type Entry = LocalTableEntry<u32, NonZeroU32>;
+        let mut table = CachedHashTable::new(HashTable::<Entry, RandomState>::new(256));
+        for i in 0..100 {
+            assert!(table
+                .insert(make_key(i), TableEntryValues::new(i, None))
+                .is_ok());
+        }
+        // a deterministic mix of repeated (hot) and scattered lookups, including missing keys
+        let mut state = 12345u32;
+        for step in 0..10_000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let v = if step % 2 == 0 {
+                7
+            } else {
+                (state >> 16) % 128
+            };
+            let cached = table.find(make_key(v)).map(|values| &*values as *const _);
+            let direct = table
+                .table()
+                .find(make_key(v))
+                .map(|values| &*values as *const _);
+            assert_eq!(cached, direct);
+        }
+        // the hot key is only evicted when a scattered lookup maps to its cache entry
+        assert!(table.hit_count() >= 3_000);
+        assert_eq!(table.hit_count() + table.miss_count(), 10_000);
+        table.reset_hit_counts();
+        assert_eq!(table.hit_count(), 0);
+        table.table_mut().clear();
+        assert!(table.find(make_key(7)).is_none());
+        assert_eq!(table.hit_count(), 0);
+    }
+
+    #[test]
+    fn test_cached_hash_table_probe_sequence() {
+        type Entry = LocalTableEntry<u32, NonZeroU32>;
+        let table: CachedHashTable<_, _, QuadraticProbeSequence> =
+            HashTable::<Entry, RandomState, _>::new(16).into();
+        for i in 0..12 {
+            assert!(table
+                .insert(make_key(i), TableEntryValues::new(i, None))
+                .is_ok());
+        }
+        // the second lookup of each present key is a hit; missing keys are
+        // never cached
+        for i in 0..16 {
+            for _ in 0..2 {
+                let found = table.find(make_key(i)).map(|values| *values.early_value());
+                assert_eq!(found, if i < 12 { Some(i) } else { None });
+            }
+        }
+        assert_eq!(table.hit_count(), 12);
+    }
+}
@@ -1,9 +1,13 @@
 use crate::hashtable_base::AlreadyFull;
+use crate::hashtable_base::HashTable;
 use crate::hashtable_base::Key;
+use crate::hashtable_base::ProbeSequence;
 use crate::hashtable_base::TableEntry;
 use crate::hashtable_base::TableEntryValues;
+use crate::hashtable_base::ZeroedIsEmpty;
 use std::cell::Cell;
 use std::cell::UnsafeCell;
+use std::hash::BuildHasher;
 use std::mem::MaybeUninit;
 use std::num::NonZeroU32;
 use std::ptr::drop_in_place;
@@ -49,21 +53,19 @@ impl<EarlyValue: 'static, LateValue: Copy + 'static> Into<(EarlyValue, Option<La
 
 pub struct LocalTableEntry<EarlyValue: 'static, LateValue: Copy + 'static> {
     key000: UnsafeCell<Option<NonZeroU32>>,
-    key001: UnsafeCell<NonZeroU32>,
-    key01: UnsafeCell<[NonZeroU32; 2]>,
-    key1: UnsafeCell<[[NonZeroU32; 2]; 2]>,
+    key001: UnsafeCell<MaybeUninit<NonZeroU32>>,
+    key01: UnsafeCell<MaybeUninit<[NonZeroU32; 2]>>,
+    key1: UnsafeCell<MaybeUninit<[[NonZeroU32; 2]; 2]>>,
     value: UnsafeCell<MaybeUninit<LocalTableValues<EarlyValue, LateValue>>>,
 }
 
 impl<EarlyValue: 'static, LateValue: Copy + 'static> LocalTableEntry<EarlyValue, LateValue> {
-    pub const EMPTY: Self = unsafe {
-        Self {
-            key000: UnsafeCell::new(None),
-            key001: UnsafeCell::new(NonZeroU32::new_unchecked(1)),
-            key01: UnsafeCell::new([NonZeroU32::new_unchecked(1); 2]),
-            key1: UnsafeCell::new([[NonZeroU32::new_unchecked(1); 2]; 2]),
-            value: UnsafeCell::new(MaybeUninit::uninit()),
-        }
+    pub const EMPTY: Self = Self {
+        key000: UnsafeCell::new(None),
+        key001: UnsafeCell::new(MaybeUninit::uninit()),
+        key01: UnsafeCell::new(MaybeUninit::uninit()),
+        key1: UnsafeCell::new(MaybeUninit::uninit()),
+        value: UnsafeCell::new(MaybeUninit::uninit()),
     };
     /// safety: self.value must not be concurrently accessed by any other threads
     unsafe fn get_value_mut_ptr(&self) -> *mut LocalTableValues<EarlyValue, LateValue> {
@@ -73,6 +75,30 @@ impl<EarlyValue: 'static, LateValue: Copy + 'static> LocalTableEntry<EarlyValue,
     unsafe fn get_value_ptr(&self) -> *const LocalTableValues<EarlyValue, LateValue> {
         (*self.value.get()).as_ptr()
     }
+    fn get_mut(&mut self) -> Option<(Key, &mut LocalTableValues<EarlyValue, LateValue>)> {
+        let (key, _) = self.get()?;
+        // safety: `get` just checked that the entry is full, and `&mut self`
+        // rules out any other access
+        Some((key, unsafe { &mut *self.get_value_mut_ptr() }))
+    }
+    /// safety: self must be empty and must not be concurrently accessed by any other threads
+    unsafe fn fill_unchecked(
+        &self,
+        key: Key,
+        value: LocalTableValues<EarlyValue, LateValue>,
+    ) -> &LocalTableValues<EarlyValue, LateValue> {
+        debug_assert!(
+            (*self.key000.get()).is_none(),
+            "LocalTableEntry filled when already full, this would leak the old value"
+        );
+        let [[[key000, key001], key01], key1] = key.0;
+        *self.key000.get() = Some(key000);
+        *self.key001.get() = MaybeUninit::new(key001);
+        *self.key01.get() = MaybeUninit::new(key01);
+        *self.key1.get() = MaybeUninit::new(key1);
+        std::ptr::write(self.get_value_mut_ptr(), value);
+        &*self.get_value_ptr()
+    }
 }
 
 impl<EarlyValue: 'static, LateValue: Copy + 'static> Drop
@@ -94,12 +120,16 @@ impl<EarlyValue: 'static, LateValue: Copy + 'static> TableEntry
     fn empty() -> Self {
         LocalTableEntry::EMPTY
     }
+    fn zeroed_is_empty() -> Option<ZeroedIsEmpty<Self>> {
+        // safety: zero is `None` for `key000`, the other fields are `MaybeUninit`
+        Some(unsafe { ZeroedIsEmpty::new() })
+    }
     fn get(&self) -> Option<(Key, &Self::Values)> {
         unsafe {
             let key000 = (*self.key000.get())?;
-            let key001 = *self.key001.get();
-            let key01 = *self.key01.get();
-            let key1 = *self.key1.get();
+            let key001 = (*self.key001.get()).assume_init();
+            let key01 = (*self.key01.get()).assume_init();
+            let key1 = (*self.key1.get()).assume_init();
             let value_ref = &*self.get_value_ptr();
             Some((Key([[[key000, key001], key01], key1]), value_ref))
         }
@@ -116,25 +146,78 @@ impl<EarlyValue: 'static, LateValue: Copy + 'static> TableEntry
                 entry_value,
             })
         } else {
-            let [[[key000, key001], key01], key1] = key.0;
-            unsafe {
-                *self.key000.get() = Some(key000);
-                *self.key001.get() = key001;
-                *self.key01.get() = key01;
-                *self.key1.get() = key1;
-                std::ptr::write(self.get_value_mut_ptr(), value);
-                Ok(&*self.get_value_ptr())
-            }
+            // safety: self was just checked to be empty
+            unsafe { Ok(self.fill_unchecked(key, value)) }
         }
     }
     fn take(&mut self) -> Option<(Key, Self::Values)> {
         unsafe {
             let key000 = ((&mut *self.key000.get()).take())?;
-            let key001 = *self.key001.get();
-            let key01 = *self.key01.get();
-            let key1 = *self.key1.get();
+            let key001 = (*self.key001.get()).assume_init();
+            let key01 = (*self.key01.get()).assume_init();
+            let key1 = (*self.key1.get()).assume_init();
             let value = std::ptr::read(self.get_value_mut_ptr());
             Some((Key([[[key000, key001], key01], key1]), value))
         }
     }
 }
+
+impl<EarlyValue: 'static, LateValue: Copy + 'static, BH: BuildHasher, P: ProbeSequence>
+    HashTable<LocalTableEntry<EarlyValue, LateValue>, BH, P>
+{
+    /// iterates over all entries in slot order, allowing each early value to
+    /// be changed in place. Entries aren't moved, so keys and explicit ids
+    /// are unaffected.
+    ///
+    /// Only the local backend has this, since sync entries publish their
+    /// early value to other threads as soon as they're filled.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Key, &mut EarlyValue)> {
+        self.table
+            .as_mut()
+            .expect("table is known to be Some")
+            .iter_mut()
+            .filter_map(|entry| {
+                let (key, values) = entry.get_mut()?;
+                Some((key, &mut values.early_value))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "LocalTableEntry filled when already full")]
+    fn test_double_fill_unchecked() {
+        let key = Key([[[NonZeroU32::new(1).unwrap(); 2]; 2]; 2]);
+        let table_entry = LocalTableEntry::<(), NonZeroU32>::empty();
+        unsafe {
+            table_entry.fill_unchecked(key, TableEntryValues::new((), None));
+            table_entry.fill_unchecked(key, TableEntryValues::new((), None));
+        }
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let make_key = |v| Key([[[NonZeroU32::new(v).unwrap(); 2]; 2]; 2]);
+        let mut table = HashTable::<LocalTableEntry<u32, NonZeroU32>, RandomState>::new(64);
+        for i in 1..=10 {
+            assert!(table
+                .insert(make_key(i), TableEntryValues::new(i, NonZeroU32::new(i)))
+                .is_ok());
+        }
+        for (key, early_value) in table.iter_mut() {
+            assert_eq!(key, make_key(*early_value));
+            *early_value *= 100;
+        }
+        assert_eq!(table.len(), 10);
+        for i in 1..=10 {
+            let values = table.find(make_key(i)).unwrap();
+            assert_eq!(*values.early_value(), i * 100);
+            assert_eq!(values.late_value(), NonZeroU32::new(i));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::hashtable_base::TableEntry;
+use memmap2::MmapMut;
+use memmap2::MmapOptions;
+use std::fs::File;
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::slice;
+
+/// table entries stored in a memory-mapped file.
+/// The entries are never dropped, they are flushed to the file instead.
+pub(crate) struct MappedEntries<Entry> {
+    mmap: MmapMut,
+    len: usize,
+    _phantom: PhantomData<Entry>,
+}
+
+impl<Entry: TableEntry> MappedEntries<Entry> {
+    /// safety: see `HashTable::with_file`
+    pub(crate) unsafe fn new(file: &File, len: usize) -> io::Result<Self> {
+        if Entry::zeroed_is_empty().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "table entry type can't be stored in a file: all-zero entries aren't empty",
+            ));
+        }
+        let byte_len = len
+            .checked_mul(mem::size_of::<Entry>())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "capacity too big"))?;
+        if file.metadata()?.len() < byte_len as u64 {
+            file.set_len(byte_len as u64)?;
+        }
+        let mmap = MmapOptions::new().len(byte_len).map_mut(file)?;
+        // mappings are page-aligned, which is more than enough for any entry
+        assert_eq!(mmap.as_ptr() as usize % mem::align_of::<Entry>(), 0);
+        Ok(Self {
+            mmap,
+            len,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<Entry> Deref for MappedEntries<Entry> {
+    type Target = [Entry];
+    fn deref(&self) -> &[Entry] {
+        // safety: the mapping is aligned and sized for `len` entries, which
+        // are valid since they started out zeroed or were written by a table
+        unsafe { slice::from_raw_parts(self.mmap.as_ptr() as *const Entry, self.len) }
+    }
+}
+
+impl<Entry> DerefMut for MappedEntries<Entry> {
+    fn deref_mut(&mut self) -> &mut [Entry] {
+        // safety: see deref
+        unsafe { slice::from_raw_parts_mut(self.mmap.as_mut_ptr() as *mut Entry, self.len) }
+    }
+}
+
+impl<Entry> Drop for MappedEntries<Entry> {
+    fn drop(&mut self) {
+        // errors can't be reported from drop; the OS still writes back the
+        // pages when they're unmapped
+        let _ = self.mmap.flush();
+    }
+}
@@ -0,0 +1,306 @@
+use crate::hashtable_base::GetOrInsertFailureReason;
+use crate::hashtable_base::GetOrInsertSuccess;
+use crate::hashtable_base::InsertFailureReason;
+use crate::hashtable_base::Key;
+use crate::hashtable_base::ProbeLengthStats;
+use std::cell::RefCell;
+use std::cell::UnsafeCell;
+use std::hash::BuildHasher;
+use std::num::NonZeroU32;
+
+#[derive(Copy, Clone)]
+struct Slot {
+    key: Key,
+    /// the entry's explicit id, its values are `values[id - 1]`
+    id: NonZeroU32,
+    /// how many slots past its hash's slot this entry is
+    distance: usize,
+}
+
+/// a single-threaded table using Robin Hood linear probing: an insert that
+/// has probed further than the entry it runs into takes that entry's slot
+/// and continues inserting the displaced entry instead. This keeps the
+/// longest probe as short as possible, so lookups in nearly full tables stay
+/// fast.
+///
+/// Inserting moves existing entries to other slots, so the slots only hold
+/// keys and explicit ids, and the values are stored separately in insertion
+/// order, where they never move. Every entry always has an explicit id:
+/// ids are handed out in insertion order starting at 1.
+pub struct RobinHoodHashTable<Values, BH: BuildHasher> {
+    slots: RefCell<Box<[Option<Slot>]>>,
+    /// allocated with room for `capacity` values up front and only ever
+    /// pushed to, so it never reallocates and references to its items stay
+    /// valid until the table is dropped
+    values: UnsafeCell<Vec<Values>>,
+    hasher: BH,
+}
+
+impl<Values, BH: BuildHasher> RobinHoodHashTable<Values, BH> {
+    pub fn with_hasher(capacity: usize, hasher: BH) -> Self {
+        let capacity = capacity
+            .checked_next_power_of_two()
+            .expect("capacity too big");
+        Self {
+            slots: RefCell::new((0..capacity).map(|_| None).collect()),
+            values: UnsafeCell::new(Vec::with_capacity(capacity)),
+            hasher,
+        }
+    }
+    pub fn new(capacity: usize) -> Self
+    where
+        BH: Default,
+    {
+        Self::with_hasher(capacity, BH::default())
+    }
+    pub fn capacity(&self) -> usize {
+        self.slots.borrow().len()
+    }
+    pub fn len(&self) -> usize {
+        // safety: `values` is only mutated by `insert`, which doesn't call
+        // back into the table while it holds a reference
+        unsafe { &*self.values.get() }.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// `len() / capacity()`
+    pub fn load_factor(&self) -> f64 {
+        self.len() as f64 / self.capacity() as f64
+    }
+    fn home_index(&self, key: Key, table_index_mask: usize) -> usize {
+        self.hasher.hash_one(key) as usize & table_index_mask
+    }
+    fn values_for_id(&self, id: NonZeroU32) -> &Values {
+        // safety: values are never moved or dropped until the table is,
+        // see `values`
+        let values = unsafe { &*self.values.get() };
+        &values[id.get() as usize - 1]
+    }
+    /// the explicit id of `key`'s entry
+    pub fn find_id(&self, key: Key) -> Option<NonZeroU32> {
+        let slots = self.slots.borrow();
+        let table_index_mask = slots.len() - 1;
+        let mut table_index = self.home_index(key, table_index_mask);
+        for distance in 0..slots.len() {
+            let slot = slots[table_index].as_ref()?;
+            if slot.distance < distance {
+                // `key` would have displaced this entry
+                return None;
+            }
+            if slot.key == key {
+                return Some(slot.id);
+            }
+            table_index = (table_index + 1) & table_index_mask;
+        }
+        None
+    }
+    pub fn find(&self, key: Key) -> Option<&Values> {
+        Some(self.values_for_id(self.find_id(key)?))
+    }
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.find_id(key).is_some()
+    }
+    pub fn insert(&self, key: Key, values: Values) -> Result<&Values, InsertFailureReason<Values>> {
+        let (_, values) = self.insert_with_id(key, values)?;
+        Ok(values)
+    }
+    fn insert_with_id(
+        &self,
+        key: Key,
+        values: Values,
+    ) -> Result<(NonZeroU32, &Values), InsertFailureReason<Values>> {
+        if let Some(id) = self.find_id(key) {
+            return Err(InsertFailureReason::AlreadyInTable {
+                passed_in_value: values,
+                entry_value: self.values_for_id(id),
+                id: Some(id),
+            });
+        }
+        let id = self.push_values(values)?;
+        let mut slots = self.slots.borrow_mut();
+        let table_index_mask = slots.len() - 1;
+        let mut table_index = self.home_index(key, table_index_mask);
+        let mut inserting = Slot {
+            key,
+            id,
+            distance: 0,
+        };
+        loop {
+            match &mut slots[table_index] {
+                slot @ None => {
+                    *slot = Some(inserting);
+                    break;
+                }
+                Some(slot) => {
+                    if slot.distance < inserting.distance {
+                        std::mem::swap(slot, &mut inserting);
+                    }
+                }
+            }
+            inserting.distance += 1;
+            table_index = (table_index + 1) & table_index_mask;
+        }
+        Ok((id, self.values_for_id(id)))
+    }
+    /// stores `values` and returns their new id, or gives them back if the
+    /// table is full
+    fn push_values(&self, values: Values) -> Result<NonZeroU32, InsertFailureReason<Values>> {
+        if self.len() == self.capacity() {
+            return Err(InsertFailureReason::TableFullOrSearchLimitHit {
+                passed_in_value: values,
+            });
+        }
+        // safety: the push doesn't reallocate since there's room for
+        // `capacity` values, so it doesn't move the values already handed out
+        let all_values = unsafe { &mut *self.values.get() };
+        all_values.push(values);
+        Ok(NonZeroU32::new(all_values.len() as u32).expect("ran out of ids"))
+    }
+    /// `GetOrInsertSuccess::id` is always the entry's explicit id
+    pub fn get_or_insert(
+        &self,
+        key: Key,
+        values: Values,
+    ) -> Result<GetOrInsertSuccess<Values>, GetOrInsertFailureReason<Values>> {
+        match self.insert_with_id(key, values) {
+            Ok((id, entry_value)) => Ok(GetOrInsertSuccess {
+                passed_in_value: None,
+                entry_value,
+                id: Some(id),
+            }),
+            Err(InsertFailureReason::AlreadyInTable {
+                passed_in_value,
+                entry_value,
+                id,
+            }) => Ok(GetOrInsertSuccess {
+                passed_in_value: Some(passed_in_value),
+                entry_value,
+                id,
+            }),
+            Err(InsertFailureReason::TableFullOrSearchLimitHit { passed_in_value }) => {
+                Err(GetOrInsertFailureReason::TableFullOrSearchLimitHit { passed_in_value })
+            }
+        }
+    }
+    /// like `get_or_insert`, but returns the explicit id of `key`'s entry
+    pub fn intern(
+        &self,
+        key: Key,
+        values: Values,
+    ) -> Result<NonZeroU32, GetOrInsertFailureReason<Values>> {
+        let success = self.get_or_insert(key, values)?;
+        Ok(success.id.expect("explicit ids are always enabled"))
+    }
+    /// every entry's explicit id, key and values, in slot order as of the call
+    pub fn iter_with_ids(&self) -> impl Iterator<Item = (NonZeroU32, Key, &Values)> {
+        let slots: Vec<Slot> = self.slots.borrow().iter().flatten().copied().collect();
+        slots
+            .into_iter()
+            .map(move |slot| (slot.id, slot.key, self.values_for_id(slot.id)))
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &Values)> {
+        self.iter_with_ids().map(|(_, key, values)| (key, values))
+    }
+    pub fn probe_length_stats(&self) -> ProbeLengthStats {
+        ProbeLengthStats::from_probe_lengths(
+            self.slots
+                .borrow()
+                .iter()
+                .filter_map(|slot| Some(slot.as_ref()?.distance + 1)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashtable_base::HashTable;
+    use crate::hashtable_base::LocalTableEntry;
+    use crate::hashtable_base::TableEntry;
+    use crate::hashtable_base::TableEntryValues;
+    use rustc_hash::FxBuildHasher;
+    use std::num::NonZeroU32;
+
+    fn make_keys(count: usize) -> Vec<Key> {
+        let mut state = 0x1234_5678u32;
+        let mut next_id = || {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            NonZeroU32::new(state).unwrap()
+        };
+        (0..count)
+            .map(|_| {
+                Key([
+                    [[next_id(), next_id()], [next_id(), next_id()]],
+                    [[next_id(), next_id()], [next_id(), next_id()]],
+                ])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_robin_hood_hash_table() {
+        let capacity = 1 << 12;
+        let keys = make_keys(capacity * 9 / 10);
+        let table = RobinHoodHashTable::<usize, FxBuildHasher>::new(capacity);
+        for (i, &key) in keys.iter().enumerate() {
+            assert_eq!(table.insert(key, i).ok().copied(), Some(i));
+        }
+        assert_eq!(table.len(), keys.len());
+        for (i, &key) in keys.iter().enumerate() {
+            assert_eq!(table.find(key), Some(&i));
+            match table.insert(key, 0) {
+                Err(InsertFailureReason::AlreadyInTable {
+                    entry_value, id, ..
+                }) => {
+                    assert_eq!(*entry_value, i);
+                    // ids are handed out in insertion order
+                    assert_eq!(id, NonZeroU32::new(i as u32 + 1));
+                }
+                _ => panic!("expected key to already be in table"),
+            }
+        }
+        for &key in &make_keys(capacity)[keys.len()..] {
+            assert!(table.find(key).is_none());
+        }
+        let mut found: Vec<_> = table.iter().map(|(_, &i)| i).collect();
+        found.sort_unstable();
+        assert_eq!(found, (0..keys.len()).collect::<Vec<_>>());
+
+        // same keys, same hasher, so both tables fill the same slots and only
+        // the order within clusters differs
+        type Entry = LocalTableEntry<(), NonZeroU32>;
+        let linear = HashTable::<Entry, FxBuildHasher>::with_search_limit(capacity, usize::MAX);
+        for &key in &keys {
+            assert!(linear
+                .insert(key, <Entry as TableEntry>::Values::new((), None))
+                .is_ok());
+        }
+        let robin_hood_stats = table.probe_length_stats();
+        let linear_stats = linear.probe_length_stats();
+        assert!(robin_hood_stats.max <= linear_stats.max);
+        assert!((robin_hood_stats.mean - linear_stats.mean).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_robin_hood_full() {
+        let keys = make_keys(5);
+        let table = RobinHoodHashTable::<usize, FxBuildHasher>::new(4);
+        for (i, &key) in keys[..4].iter().enumerate() {
+            assert!(table.insert(key, i).is_ok());
+        }
+        match table.insert(keys[4], 4) {
+            Err(InsertFailureReason::TableFullOrSearchLimitHit { passed_in_value }) => {
+                assert_eq!(passed_in_value, 4)
+            }
+            _ => panic!("expected table to be full"),
+        }
+        for (i, &key) in keys[..4].iter().enumerate() {
+            assert_eq!(table.find(key), Some(&i));
+        }
+        assert!(table.probe_length_stats().max <= 4);
+    }
+}
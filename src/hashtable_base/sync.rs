@@ -2,24 +2,114 @@ use crate::hashtable_base::AlreadyFull;
 use crate::hashtable_base::Key;
 use crate::hashtable_base::TableEntry;
 use crate::hashtable_base::TableEntryValues;
-use std::cell::UnsafeCell;
+use crate::hashtable_base::ZeroedIsEmpty;
+#[cfg(loom)]
+use loom::cell::UnsafeCell;
+#[cfg(loom)]
+use loom::sync::atomic::spin_loop_hint;
+#[cfg(loom)]
+use loom::sync::atomic::AtomicU32;
+#[cfg(loom)]
+use loom::sync::atomic::AtomicU64;
+#[cfg(loom)]
+use loom::thread::yield_now;
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::num::NonZeroU32;
 use std::ptr::drop_in_place;
+#[cfg(not(loom))]
 use std::sync::atomic::spin_loop_hint;
+#[cfg(not(loom))]
 use std::sync::atomic::AtomicU32;
+#[cfg(not(loom))]
 use std::sync::atomic::AtomicU64;
+#[cfg(feature = "stats")]
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
+#[cfg(not(loom))]
+use std::thread::yield_now;
+
+#[cfg(feature = "stats")]
+static BACKOFF_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// how many times `SyncTableEntry::get` found an entry that another thread
+/// was still filling and had to back off, summed over all tables; a measure
+/// of contention. Always 0 unless the `stats` feature is enabled.
+pub fn backoff_count() -> usize {
+    #[cfg(feature = "stats")]
+    {
+        BACKOFF_COUNT.load(Ordering::Relaxed)
+    }
+    #[cfg(not(feature = "stats"))]
+    {
+        0
+    }
+}
+
+/// `std::cell::UnsafeCell` with the closure-based accessors of
+/// `loom::cell::UnsafeCell`, so the entry's code is the same whether or not
+/// it is being model-checked
+#[cfg(not(loom))]
+#[repr(transparent)]
+struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    const fn new(value: T) -> Self {
+        UnsafeCell(std::cell::UnsafeCell::new(value))
+    }
+    fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+    fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
+
+/// memory orderings used to load and store late values
+pub trait LateValueOrdering: 'static {
+    const LOAD: Ordering;
+    const STORE: Ordering;
+}
+
+/// `Acquire` loads and `Release` stores: reading a late value also makes
+/// visible everything its writer did before storing it, such as inserting
+/// the node the late value refers to. Always sound, and the default.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AcquireReleaseOrdering;
+
+impl LateValueOrdering for AcquireReleaseOrdering {
+    const LOAD: Ordering = Ordering::Acquire;
+    const STORE: Ordering = Ordering::Release;
+}
+
+/// `Relaxed` loads and stores.
+///
+/// Only sound if every read of a late value already happens-after the
+/// writes it depends on through some other synchronization, such as a
+/// barrier or thread join between the step phase and the read phase.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RelaxedOrdering;
+
+impl LateValueOrdering for RelaxedOrdering {
+    const LOAD: Ordering = Ordering::Relaxed;
+    const STORE: Ordering = Ordering::Relaxed;
+}
 
 /// `LateValue` must be `NonZeroU32`
-pub struct SyncTableValues<EarlyValue: 'static, LateValue: Copy + 'static> {
+pub struct SyncTableValues<
+    EarlyValue: 'static,
+    LateValue: Copy + 'static,
+    O: LateValueOrdering = AcquireReleaseOrdering,
+> {
     early_value: EarlyValue,
     late_value: AtomicU32,
-    _phantom: PhantomData<LateValue>,
+    _phantom: PhantomData<(LateValue, O)>,
 }
 
-impl<EarlyValue: 'static> TableEntryValues for SyncTableValues<EarlyValue, NonZeroU32> {
+impl<EarlyValue: 'static, O: LateValueOrdering> TableEntryValues
+    for SyncTableValues<EarlyValue, NonZeroU32, O>
+{
     type LateValue = NonZeroU32;
     type EarlyValue = EarlyValue;
     fn new(early_value: Self::EarlyValue, late_value: Option<Self::LateValue>) -> Self {
@@ -33,18 +123,19 @@ impl<EarlyValue: 'static> TableEntryValues for SyncTableValues<EarlyValue, NonZe
         &self.early_value
     }
     fn late_value(&self) -> Option<Self::LateValue> {
-        NonZeroU32::new(self.late_value.load(Ordering::Acquire))
+        NonZeroU32::new(self.late_value.load(O::LOAD))
+    }
+    fn peek_late_value(&self) -> Option<Self::LateValue> {
+        NonZeroU32::new(self.late_value.load(Ordering::Relaxed))
     }
     fn set_late_value(&self, late_value: Option<Self::LateValue>) {
-        self.late_value.store(
-            late_value.map(NonZeroU32::get).unwrap_or(0),
-            Ordering::Release,
-        );
+        self.late_value
+            .store(late_value.map(NonZeroU32::get).unwrap_or(0), O::STORE);
     }
 }
 
-impl<EarlyValue: 'static> Into<(EarlyValue, Option<NonZeroU32>)>
-    for SyncTableValues<EarlyValue, NonZeroU32>
+impl<EarlyValue: 'static, O: LateValueOrdering> Into<(EarlyValue, Option<NonZeroU32>)>
+    for SyncTableValues<EarlyValue, NonZeroU32, O>
 {
     fn into(self) -> (EarlyValue, Option<NonZeroU32>) {
         let Self {
@@ -52,25 +143,47 @@ impl<EarlyValue: 'static> Into<(EarlyValue, Option<NonZeroU32>)>
             late_value,
             _phantom,
         } = self;
-        (early_value, NonZeroU32::new(late_value.into_inner()))
+        // `self` is owned, so no other thread can be storing to `late_value`
+        (
+            early_value,
+            NonZeroU32::new(late_value.load(Ordering::Relaxed)),
+        )
     }
 }
 
-pub struct SyncTableEntry<EarlyValue: 'static, LateValue: Copy + 'static> {
+/// `repr(C)` keeps the fields `find` reads on every probe (`state`, `key01`
+/// and `key1`) together in the first 32 bytes, ahead of the value.
+///
+/// `state` already packs `key00` and the modification flag into one word,
+/// using the fact that ids are never zero. There are no spare bits left for a
+/// hash tag without narrowing ids, so with the leaf table's `()` early value
+/// the entry is 36 bytes of data padded to 40.
+#[repr(C)]
+pub struct SyncTableEntry<
+    EarlyValue: 'static,
+    LateValue: Copy + 'static,
+    O: LateValueOrdering = AcquireReleaseOrdering,
+> {
     state: AtomicU64,
-    key01: UnsafeCell<[NonZeroU32; 2]>,
-    key1: UnsafeCell<[[NonZeroU32; 2]; 2]>,
-    value: UnsafeCell<MaybeUninit<SyncTableValues<EarlyValue, LateValue>>>,
+    key01: UnsafeCell<MaybeUninit<[NonZeroU32; 2]>>,
+    key1: UnsafeCell<MaybeUninit<[[NonZeroU32; 2]; 2]>>,
+    value: UnsafeCell<MaybeUninit<SyncTableValues<EarlyValue, LateValue, O>>>,
 }
 
-unsafe impl<EarlyValue: 'static + Send + Sync, LateValue: Copy + 'static + Send + Sync> Sync
-    for SyncTableEntry<EarlyValue, LateValue>
+unsafe impl<
+        EarlyValue: 'static + Send + Sync,
+        LateValue: Copy + 'static + Send + Sync,
+        O: LateValueOrdering,
+    > Sync for SyncTableEntry<EarlyValue, LateValue, O>
 {
 }
 
-impl<EarlyValue, LateValue: Copy> Drop for SyncTableEntry<EarlyValue, LateValue> {
+impl<EarlyValue, LateValue: Copy, O: LateValueOrdering> Drop
+    for SyncTableEntry<EarlyValue, LateValue, O>
+{
     fn drop(&mut self) {
-        match State::from(*self.state.get_mut()) {
+        // `&mut self` excludes every other access, so `Relaxed` is enough
+        match State::from(self.state.load(Ordering::Relaxed)) {
             State::Empty => {}
             State::ModificationInProgress => unreachable!("invalid state"),
             State::Full { .. } => unsafe { drop_in_place(self.get_value_mut_ptr()) },
@@ -78,94 +191,151 @@ impl<EarlyValue, LateValue: Copy> Drop for SyncTableEntry<EarlyValue, LateValue>
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum State {
     Empty,
     ModificationInProgress,
     Full { key00: [NonZeroU32; 2] },
 }
 
-#[cfg(target_endian = "big")]
-const fn unpack_u64(v: u64) -> [u32; 2] {
-    [(v >> 32) as u32, v as u32]
+/// byte order used to pack the state word.
+/// Only `Endian::NATIVE` is used outside of tests; the other is available so
+/// both code paths can be tested on any host.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Endian {
+    Big,
+    Little,
 }
 
-#[cfg(not(target_endian = "big"))]
-const fn unpack_u64(v: u64) -> [u32; 2] {
-    [v as u32, (v >> 32) as u32]
+impl Endian {
+    #[cfg(target_endian = "big")]
+    const NATIVE: Endian = Endian::Big;
+    #[cfg(not(target_endian = "big"))]
+    const NATIVE: Endian = Endian::Little;
 }
 
-#[cfg(target_endian = "big")]
-const fn pack_u64(v: [u32; 2]) -> u64 {
+const fn unpack_u64_with_endian(v: u64, endian: Endian) -> [u32; 2] {
+    match endian {
+        Endian::Big => [(v >> 32) as u32, v as u32],
+        Endian::Little => [v as u32, (v >> 32) as u32],
+    }
+}
+
+const fn pack_u64_with_endian(v: [u32; 2], endian: Endian) -> u64 {
     let [v0, v1] = v;
-    ((v0 as u64) << 32) + v1 as u64
+    match endian {
+        Endian::Big => ((v0 as u64) << 32) + v1 as u64,
+        Endian::Little => v0 as u64 + ((v1 as u64) << 32),
+    }
+}
+
+const fn unpack_u64(v: u64) -> [u32; 2] {
+    unpack_u64_with_endian(v, Endian::NATIVE)
 }
 
-#[cfg(not(target_endian = "big"))]
 const fn pack_u64(v: [u32; 2]) -> u64 {
-    let [v0, v1] = v;
-    v0 as u64 + ((v1 as u64) << 32)
+    pack_u64_with_endian(v, Endian::NATIVE)
 }
 
 impl State {
     const EMPTY_U64: u64 = pack_u64([0, 0]);
     const MODIFICATION_IN_PROGRESS_U64: u64 = pack_u64([1, 0]);
+    fn into_u64_with_endian(self, endian: Endian) -> u64 {
+        match self {
+            State::Empty => pack_u64_with_endian([0, 0], endian),
+            State::ModificationInProgress => pack_u64_with_endian([1, 0], endian),
+            State::Full { key00: [u0, u1] } => pack_u64_with_endian([u0.get(), u1.get()], endian),
+        }
+    }
+    fn from_u64_with_endian(v: u64, endian: Endian) -> State {
+        match unpack_u64_with_endian(v, endian) {
+            [0, 0] => State::Empty,
+            [1, 0] => State::ModificationInProgress,
+            [u0, u1] => State::Full {
+                key00: [
+                    NonZeroU32::new(u0).expect("invalid state"),
+                    NonZeroU32::new(u1).expect("invalid state"),
+                ],
+            },
+        }
+    }
 }
 
 impl From<State> for u64 {
     fn from(v: State) -> u64 {
-        match v {
-            State::Empty => State::EMPTY_U64,
-            State::ModificationInProgress => State::MODIFICATION_IN_PROGRESS_U64,
-            State::Full { key00: [u0, u1] } => pack_u64([u0.get(), u1.get()]),
-        }
+        v.into_u64_with_endian(Endian::NATIVE)
     }
 }
 
 impl From<u64> for State {
     fn from(v: u64) -> State {
-        let [u0, u1] = unpack_u64(v);
-        match v {
-            State::EMPTY_U64 => State::Empty,
-            State::MODIFICATION_IN_PROGRESS_U64 => State::ModificationInProgress,
-            _ => State::Full {
-                key00: [
-                    NonZeroU32::new(u0).expect("invalid state"),
-                    NonZeroU32::new(u1).expect("invalid state"),
-                ],
-            },
-        }
+        State::from_u64_with_endian(v, Endian::NATIVE)
     }
 }
 
-impl<EarlyValue: 'static, LateValue: 'static + Copy> SyncTableEntry<EarlyValue, LateValue> {
-    pub const EMPTY: Self = unsafe {
-        Self {
-            state: AtomicU64::new(0),
-            key01: UnsafeCell::new([NonZeroU32::new_unchecked(1); 2]),
-            key1: UnsafeCell::new([[NonZeroU32::new_unchecked(1); 2]; 2]),
-            value: UnsafeCell::new(MaybeUninit::uninit()),
-        }
+impl<EarlyValue: 'static, LateValue: 'static + Copy, O: LateValueOrdering>
+    SyncTableEntry<EarlyValue, LateValue, O>
+{
+    /// whether an entry made entirely of zero bytes is `EMPTY`, which lets
+    /// tables use zeroed allocations (and zero-filled files) instead of
+    /// writing `EMPTY` to every entry. Holds because the empty state word is
+    /// 0 and every other field is `MaybeUninit`; any change to the entry's
+    /// layout has to keep both true or make this false. Never true under
+    /// loom, whose atomics and cells aren't plain data.
+    pub const ALL_ZERO_IS_EMPTY: bool = !cfg!(loom) && State::EMPTY_U64 == 0;
+    #[cfg(not(loom))]
+    pub const EMPTY: Self = Self {
+        state: AtomicU64::new(State::EMPTY_U64),
+        key01: UnsafeCell::new(MaybeUninit::uninit()),
+        key1: UnsafeCell::new(MaybeUninit::uninit()),
+        value: UnsafeCell::new(MaybeUninit::uninit()),
     };
     /// safety: self.value must not be concurrently accessed by any other threads
-    unsafe fn get_value_mut_ptr(&self) -> *mut SyncTableValues<EarlyValue, LateValue> {
-        (*self.value.get()).as_mut_ptr()
+    unsafe fn get_value_mut_ptr(&self) -> *mut SyncTableValues<EarlyValue, LateValue, O> {
+        self.value.with_mut(|value| (*value).as_mut_ptr())
     }
     /// safety: self.value must not be concurrently written by any other threads
-    unsafe fn get_value_ptr(&self) -> *const SyncTableValues<EarlyValue, LateValue> {
-        (*self.value.get()).as_ptr()
+    unsafe fn get_value_ptr(&self) -> *const SyncTableValues<EarlyValue, LateValue, O> {
+        self.value.with(|value| (*value).as_ptr())
+    }
+    /// safety: the keys must have been written by a `fill` that has finished
+    unsafe fn get_key01_and_key1(&self) -> ([NonZeroU32; 2], [[NonZeroU32; 2]; 2]) {
+        let key01 = self.key01.with(|key01| (*key01).assume_init());
+        let key1 = self.key1.with(|key1| (*key1).assume_init());
+        (key01, key1)
     }
 }
 
-impl<EarlyValue: 'static, LateValue: Copy + 'static> TableEntry
-    for SyncTableEntry<EarlyValue, LateValue>
+impl<EarlyValue: 'static, LateValue: Copy + 'static, O: LateValueOrdering> TableEntry
+    for SyncTableEntry<EarlyValue, LateValue, O>
 where
-    SyncTableValues<EarlyValue, LateValue>:
+    SyncTableValues<EarlyValue, LateValue, O>:
         TableEntryValues<EarlyValue = EarlyValue, LateValue = LateValue>,
 {
-    type Values = SyncTableValues<EarlyValue, LateValue>;
+    type Values = SyncTableValues<EarlyValue, LateValue, O>;
     fn empty() -> Self {
-        SyncTableEntry::EMPTY
+        #[cfg(not(loom))]
+        {
+            SyncTableEntry::EMPTY
+        }
+        // loom's atomics and cells can't be created in a const
+        #[cfg(loom)]
+        {
+            SyncTableEntry {
+                state: AtomicU64::new(State::EMPTY_U64),
+                key01: UnsafeCell::new(MaybeUninit::uninit()),
+                key1: UnsafeCell::new(MaybeUninit::uninit()),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            }
+        }
+    }
+    fn zeroed_is_empty() -> Option<ZeroedIsEmpty<Self>> {
+        if Self::ALL_ZERO_IS_EMPTY {
+            // safety: zero is `State::Empty`, the other fields are `MaybeUninit`
+            Some(unsafe { ZeroedIsEmpty::new() })
+        } else {
+            None
+        }
     }
     fn get(&self) -> Option<(Key, &Self::Values)> {
         let mut backoff_step = 0;
@@ -174,24 +344,47 @@ where
                 State::Empty => return None,
                 State::Full { key00 } => break key00,
                 State::ModificationInProgress => {
+                    #[cfg(feature = "stats")]
+                    {
+                        if backoff_step == 0 {
+                            BACKOFF_COUNT.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
                     if backoff_step <= 6 {
                         for _ in 0..(1 << backoff_step) {
                             spin_loop_hint()
                         }
                         backoff_step += 1;
                     } else {
-                        std::thread::yield_now();
+                        yield_now();
                     }
                 }
             }
         };
         // safety: state will never transition from Full to something else while self is shared
         unsafe {
-            let key01 = *self.key01.get();
-            let key1 = *self.key1.get();
+            let (key01, key1) = self.get_key01_and_key1();
             Some((Key([[key00, key01], key1]), &*self.get_value_ptr()))
         }
     }
+    /// reads the state with a single `Relaxed` load and no backoff
+    unsafe fn get_unsynchronized(&self) -> Option<(Key, &Self::Values)> {
+        let key00 = match State::from(self.state.load(Ordering::Relaxed)) {
+            State::Empty => return None,
+            State::Full { key00 } => key00,
+            State::ModificationInProgress => {
+                unreachable!("get_unsynchronized called while the entry is being filled")
+            }
+        };
+        let (key01, key1) = self.get_key01_and_key1();
+        Some((Key([[key00, key01], key1]), &*self.get_value_ptr()))
+    }
+    fn peek_key00(&self) -> Option<[NonZeroU32; 2]> {
+        match State::from(self.state.load(Ordering::Acquire)) {
+            State::Full { key00 } => Some(key00),
+            State::Empty | State::ModificationInProgress => None,
+        }
+    }
     fn fill(
         &self,
         key: Key,
@@ -225,8 +418,7 @@ where
                     });
                 }
                 Err(State::Full { key00 }) => unsafe {
-                    let key01 = *self.key01.get();
-                    let key1 = *self.key1.get();
+                    let (key01, key1) = self.get_key01_and_key1();
                     let entry_key = Key([[key00, key01], key1]);
                     return Err(AlreadyFull {
                         passed_in_value: value,
@@ -239,8 +431,8 @@ where
         let [[key00, key01], key1] = key.0;
         // safety: state is currently ModificationInProgress, which will block all concurrent accesses until state is stored to
         unsafe {
-            *self.key01.get() = key01;
-            *self.key1.get() = key1;
+            self.key01.with_mut(|v| *v = MaybeUninit::new(key01));
+            self.key1.with_mut(|v| *v = MaybeUninit::new(key1));
             std::ptr::write(self.get_value_mut_ptr(), value);
             // finish modifying
             self.state
@@ -250,13 +442,13 @@ where
     }
     fn take(&mut self) -> Option<(Key, Self::Values)> {
         unsafe {
-            match State::from(*self.state.get_mut()) {
+            // `&mut self` excludes every other access, so `Relaxed` is enough
+            match State::from(self.state.load(Ordering::Relaxed)) {
                 State::Empty => None,
                 State::ModificationInProgress => unreachable!("invalid state"),
                 State::Full { key00 } => {
-                    *self.state.get_mut() = State::Empty.into();
-                    let key01 = *self.key01.get();
-                    let key1 = *self.key1.get();
+                    self.state.store(State::EMPTY_U64, Ordering::Relaxed);
+                    let (key01, key1) = self.get_key01_and_key1();
                     let value = std::ptr::read(self.get_value_mut_ptr());
                     Some((Key([[key00, key01], key1]), value))
                 }
@@ -269,6 +461,38 @@ where
 mod tests {
     use super::*;
 
+    #[cfg(not(loom))]
+    #[test]
+    fn test_zeroed_entry_is_empty() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        // `zeroed_is_empty` is `Some` exactly when `ALL_ZERO_IS_EMPTY` is true
+        assert!(Entry::zeroed_is_empty().is_some());
+        // safety: `ALL_ZERO_IS_EMPTY` says this is a valid, empty entry
+        let mut entry: Entry = unsafe { MaybeUninit::zeroed().assume_init() };
+        assert!(entry.get().is_none());
+        assert!(entry.peek_key00().is_none());
+        assert!(entry.take().is_none());
+        let key = Key([[[NonZeroU32::new(3).unwrap(); 2]; 2]; 2]);
+        assert!(entry
+            .fill(key, <Entry as TableEntry>::Values::new(5, None))
+            .is_ok());
+        assert_eq!(
+            entry
+                .take()
+                .map(|(key, values)| (key, *values.early_value())),
+            Some((key, 5))
+        );
+    }
+
+    #[cfg(not(loom))]
+    #[test]
+    fn test_entry_size() {
+        use std::mem::size_of;
+        assert_eq!(size_of::<SyncTableEntry<(), NonZeroU32>>(), 40);
+        assert_eq!(size_of::<SyncTableEntry<u32, NonZeroU32>>(), 40);
+        assert_eq!(size_of::<SyncTableEntry<u64, NonZeroU32>>(), 48);
+    }
+
     #[test]
     fn test_pack_unpack() {
         assert_eq!(unpack_u64(pack_u64([1, 2])), [1, 2]);
@@ -285,4 +509,191 @@ mod tests {
         let [u0, u1] = unpack_u64(State::MODIFICATION_IN_PROGRESS_U64);
         assert!(u0 == 0 || u1 == 0);
     }
+
+    /// The `Endian::Big` path only runs natively on big-endian hosts, so
+    /// these tests force both orderings. To also run the whole suite on a
+    /// big-endian target, use [cross](https://github.com/rust-embedded/cross):
+    /// `cross test --target mips64-unknown-linux-gnuabi64`
+    #[test]
+    fn test_pack_unpack_both_endians() {
+        assert_eq!(
+            pack_u64_with_endian([1, 2], Endian::Big),
+            0x0000_0001_0000_0002
+        );
+        assert_eq!(
+            pack_u64_with_endian([1, 2], Endian::Little),
+            0x0000_0002_0000_0001
+        );
+        for &endian in &[Endian::Big, Endian::Little] {
+            for &v in &[[1, 2], [0, 5], [5, 0], [0xFFFF_FFFF, 0x8000_0001]] {
+                assert_eq!(
+                    unpack_u64_with_endian(pack_u64_with_endian(v, endian), endian),
+                    v
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip_both_endians() {
+        let full = |u0, u1| State::Full {
+            key00: [NonZeroU32::new(u0).unwrap(), NonZeroU32::new(u1).unwrap()],
+        };
+        for &endian in &[Endian::Big, Endian::Little] {
+            for &state in &[
+                State::Empty,
+                State::ModificationInProgress,
+                full(1, 1),
+                full(1, 2),
+                full(2, 1),
+                full(0xFFFF_FFFF, 0x1234_5678),
+            ] {
+                let v = state.into_u64_with_endian(endian);
+                assert_eq!(State::from_u64_with_endian(v, endian), state);
+            }
+            assert_eq!(State::Empty.into_u64_with_endian(endian), 0);
+        }
+        assert_eq!(u64::from(State::Empty), State::EMPTY_U64);
+        assert_eq!(
+            u64::from(State::ModificationInProgress),
+            State::MODIFICATION_IN_PROGRESS_U64
+        );
+    }
+
+    /// a key with every child set to `id`
+    #[cfg(loom)]
+    fn loom_key(id: u32) -> Key {
+        Key([[[NonZeroU32::new(id).unwrap(); 2]; 2]; 2])
+    }
+
+    /// a parent entry and a node entry, with a writer thread that inserts
+    /// the node and then stores its id as the parent's late value, the way a
+    /// step publishes its result. Must be created inside `loom::model`.
+    #[cfg(loom)]
+    struct LoomFixture<O: LateValueOrdering> {
+        parent: loom::sync::Arc<SyncTableEntry<u32, NonZeroU32, O>>,
+        node: loom::sync::Arc<SyncTableEntry<u32, NonZeroU32, O>>,
+        writer: Option<loom::thread::JoinHandle<()>>,
+    }
+
+    #[cfg(loom)]
+    impl<O: LateValueOrdering> LoomFixture<O> {
+        const PARENT_ID: u32 = 1;
+        const NODE_ID: u32 = 2;
+        fn spawn_writer() -> Self {
+            let parent = loom::sync::Arc::new(SyncTableEntry::empty());
+            let node = loom::sync::Arc::new(SyncTableEntry::empty());
+            let values = SyncTableValues::new(Self::PARENT_ID, None);
+            assert!(parent.fill(loom_key(Self::PARENT_ID), values).is_ok());
+            let writer = {
+                let parent = parent.clone();
+                let node = node.clone();
+                loom::thread::spawn(move || {
+                    let values = SyncTableValues::new(Self::NODE_ID, None);
+                    assert!(node.fill(loom_key(Self::NODE_ID), values).is_ok());
+                    parent
+                        .get()
+                        .unwrap()
+                        .1
+                        .set_late_value(NonZeroU32::new(Self::NODE_ID));
+                })
+            };
+            LoomFixture {
+                parent,
+                node,
+                writer: Some(writer),
+            }
+        }
+        fn join_writer(&mut self) {
+            self.writer.take().unwrap().join().unwrap();
+        }
+        fn parent_values(&self) -> &SyncTableValues<u32, NonZeroU32, O> {
+            self.parent.get().unwrap().1
+        }
+        fn assert_node_visible(&self) {
+            let (key, values) = self
+                .node
+                .get()
+                .expect("late value is visible but the node it refers to isn't");
+            assert_eq!(key, loom_key(Self::NODE_ID));
+            assert_eq!(*values.early_value(), Self::NODE_ID);
+        }
+    }
+
+    /// a late value is the id of a node that its writer inserted first, so
+    /// a reader that sees the late value must also see that node.
+    ///
+    /// run with `RUSTFLAGS="--cfg loom" cargo test --release loom_`
+    #[cfg(loom)]
+    #[test]
+    fn loom_default_late_value_ordering() {
+        loom::model(|| {
+            let mut fixture = LoomFixture::<AcquireReleaseOrdering>::spawn_writer();
+            if fixture.parent_values().late_value().is_some() {
+                fixture.assert_node_visible();
+            }
+            fixture.join_writer();
+        });
+    }
+
+    /// two threads filling the same entry: exactly one wins, and the other
+    /// sees the winner's key and value, waiting for it if still in progress
+    #[cfg(loom)]
+    #[test]
+    fn loom_fill_race() {
+        type Entry = SyncTableEntry<u32, NonZeroU32>;
+        /// fills `entry` with `id` as both key and value, returning the id
+        /// the entry ended up with
+        fn fill(entry: &Entry, id: u32) -> u32 {
+            let key = loom_key(id);
+            match entry.fill(key, SyncTableValues::new(id, None)) {
+                Ok(values) => *values.early_value(),
+                Err(AlreadyFull {
+                    entry_key,
+                    entry_value,
+                    ..
+                }) => {
+                    let winner = *entry_value.early_value();
+                    assert_eq!(entry_key, loom_key(winner));
+                    winner
+                }
+            }
+        }
+        loom::model(|| {
+            let entry = loom::sync::Arc::new(Entry::empty());
+            let other = {
+                let entry = entry.clone();
+                loom::thread::spawn(move || fill(&entry, 2))
+            };
+            let winner = fill(&entry, 1);
+            assert_eq!(other.join().unwrap(), winner);
+        });
+    }
+
+    /// `peek_late_value` after joining the writer sees the node
+    #[cfg(loom)]
+    #[test]
+    fn loom_peek_late_value_after_join() {
+        loom::model(|| {
+            let mut fixture = LoomFixture::<AcquireReleaseOrdering>::spawn_writer();
+            fixture.join_writer();
+            assert!(fixture.parent_values().peek_late_value().is_some());
+            fixture.assert_node_visible();
+        });
+    }
+
+    /// negative example: `peek_late_value` racing with the writer can see the
+    /// late value without seeing the node it refers to
+    #[cfg(loom)]
+    #[test]
+    #[should_panic(expected = "late value is visible but the node it refers to isn't")]
+    fn loom_peek_late_value_without_barrier() {
+        loom::model(|| {
+            let mut fixture = LoomFixture::<AcquireReleaseOrdering>::spawn_writer();
+            if fixture.parent_values().peek_late_value().is_some() {
+                fixture.assert_node_visible();
+            }
+            fixture.join_writer();
+        });
+    }
 }
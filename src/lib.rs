@@ -13,7 +13,13 @@ macro_rules! impl_everything {
     };
 }
 
+pub mod bounding_box;
 pub mod common;
+pub mod hashlife;
+pub mod hashtable;
+pub mod hashtable_base;
+pub mod random;
+pub mod rules;
 
 impl_everything!(sync);
 impl_everything!(unsync);
@@ -1,2 +1,9 @@
+//! `MaybeSend` and `MaybeSync` are `Send` and `Sync`, so anything shared
+//! between the threads of the parallel step has to be thread-safe:
+//!
+//! ```compile_fail,E0277
+//! fn assert_maybe_send<T: parallel_hashlife_rust::sync::MaybeSend>() {}
+//! assert_maybe_send::<std::rc::Rc<()>>();
+//! ```
 pub use std::marker::Send as MaybeSend;
 pub use std::marker::Sync as MaybeSync;
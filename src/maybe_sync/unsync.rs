@@ -1,3 +1,10 @@
+//! `MaybeSend` and `MaybeSync` are implemented for every type, so
+//! single-threaded code can use types that aren't thread-safe:
+//!
+//! ```
+//! fn assert_maybe_send<T: parallel_hashlife_rust::unsync::MaybeSend>() {}
+//! assert_maybe_send::<std::rc::Rc<()>>();
+//! ```
 pub trait FakeSend {}
 pub trait FakeSync {}
 
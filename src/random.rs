@@ -0,0 +1,65 @@
+//! deterministic pseudo-random patterns for reproducible tests and
+//! benchmarks, without depending on `rand`
+
+use crate::bounding_box::BoundingBox;
+
+/// the SplitMix64 generator. Only integer arithmetic with defined overflow,
+/// so a seed gives the same sequence on every platform.
+#[derive(Clone, Debug)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// `true` with probability `probability`; 0 or less is never and 1 or
+    /// more is always. Compares the top 53 bits against `probability`
+    /// scaled by `2^53`, which is exact, so this is deterministic too.
+    pub fn next_bool(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64) < probability * (1u64 << 53) as f64
+    }
+}
+
+/// the live cells of a random pattern filling `region`, where each cell is
+/// alive with probability `density`, in the order of `BoundingBox::cells`.
+/// The same arguments always give the same cells.
+pub fn random_cells(seed: u64, region: BoundingBox, density: f64) -> Vec<[i64; 3]> {
+    let mut rng = SplitMix64::new(seed);
+    region.cells().filter(|_| rng.next_bool(density)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_mix_64() {
+        // reference outputs for seed 0
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+        assert_eq!(rng.next_u64(), 0x06C4_5D18_8009_454F);
+    }
+
+    #[test]
+    fn test_random_cells() {
+        let region = BoundingBox::new([-8, -8, -8], [7, 7, 7]);
+        let cells = random_cells(12345, region, 0.25);
+        assert_eq!(cells, random_cells(12345, region, 0.25));
+        assert_ne!(cells, random_cells(12346, region, 0.25));
+        assert!(cells.iter().all(|&cell| region.contains(cell)));
+        // 4096 cells, so the count is within a few standard deviations of 1024
+        assert!((900..1150).contains(&cells.len()), "{}", cells.len());
+        assert!(random_cells(1, region, 0.0).is_empty());
+        assert_eq!(random_cells(1, region, 1.0).len(), 4096);
+    }
+}
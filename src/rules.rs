@@ -0,0 +1,174 @@
+use crate::hashtable::FailureReason;
+use crate::hashtable::HashTables;
+use crate::hashtable::Id;
+use crate::hashtable::Leaf;
+use crate::hashtable::Level1;
+use crate::hashtable::TableEntry;
+use crate::hashtable_base::TableEntryValues as TableEntryValuesBase;
+use std::hash::BuildHasher;
+use std::num::NonZeroU32;
+
+/// 3D life-like rule B6/S567: a dead cell with exactly 6 live neighbors
+/// becomes alive, a live cell with 5, 6, or 7 live neighbors stays alive, and
+/// every other cell is dead in the next generation. Neighbors are the 26
+/// cells of the surrounding 3x3x3 cube.
+///
+/// Leaf ids are cell states: `Conway3D::ALIVE` is alive, every other id is dead.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Conway3D;
+
+impl Conway3D {
+    pub const DEAD: u32 = 1;
+    pub const ALIVE: u32 = 2;
+    pub fn cell(alive: bool) -> Id<Leaf> {
+        let state = if alive { Self::ALIVE } else { Self::DEAD };
+        Id::from(NonZeroU32::new(state).unwrap())
+    }
+    pub fn is_alive(cell: Id<Leaf>) -> bool {
+        NonZeroU32::from(cell).get() == Self::ALIVE
+    }
+    pub fn next_alive(alive: bool, live_neighbors: usize) -> bool {
+        match live_neighbors {
+            6 => true,
+            5 | 7 => alive,
+            _ => false,
+        }
+    }
+    /// `state[x][y][z]`, the center cell is `state[1][1][1]`
+    pub fn next_state(state: [[[Id<Leaf>; 3]; 3]; 3]) -> Id<Leaf> {
+        let live_cells = state
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(|&&cell| Self::is_alive(cell))
+            .count();
+        let alive = Self::is_alive(state[1][1][1]);
+        let live_neighbors = live_cells - alive as usize;
+        Self::cell(Self::next_alive(alive, live_neighbors))
+    }
+    /// interns the leaf node holding the 2x2x2 block of cells `cells`
+    /// (indexed `[x][y][z]`) and returns its id. The leaf table must have
+    /// explicit ids enabled.
+    pub fn leaf_from_bools<Entry: TableEntry, BH: BuildHasher>(
+        hash_tables: &HashTables<Entry, BH>,
+        cells: [[[bool; 2]; 2]; 2],
+    ) -> Result<Id<Level1>, FailureReason>
+    where
+        Entry::Values: TableEntryValuesBase<LateValue = NonZeroU32>,
+        <Entry::Values as TableEntryValuesBase>::EarlyValue: Default,
+    {
+        let mut children = [[[Self::cell(false); 2]; 2]; 2];
+        for (x, plane) in children.iter_mut().enumerate() {
+            for (y, row) in plane.iter_mut().enumerate() {
+                for (z, cell) in row.iter_mut().enumerate() {
+                    *cell = Self::cell(cells[x][y][z]);
+                }
+            }
+        }
+        hash_tables.get_or_insert_node::<Level1>(children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashtable::Key;
+    use crate::hashtable_base::SyncTableEntry;
+    use rustc_hash::FxBuildHasher;
+
+    /// builds a neighborhood with the center cell and the first
+    /// `live_neighbors` of the other cells (in `[x][y][z]` order) alive
+    fn neighborhood(center_alive: bool, live_neighbors: usize) -> [[[Id<Leaf>; 3]; 3]; 3] {
+        let mut state = [[[Conway3D::cell(false); 3]; 3]; 3];
+        let mut remaining = live_neighbors;
+        for (x, plane) in state.iter_mut().enumerate() {
+            for (y, row) in plane.iter_mut().enumerate() {
+                for (z, cell) in row.iter_mut().enumerate() {
+                    if (x, y, z) == (1, 1, 1) {
+                        *cell = Conway3D::cell(center_alive);
+                    } else if remaining > 0 {
+                        *cell = Conway3D::cell(true);
+                        remaining -= 1;
+                    }
+                }
+            }
+        }
+        state
+    }
+
+    #[test]
+    fn test_conway_3d() {
+        // (center alive, live neighbors, next alive)
+        let transitions = [
+            (false, 0, false),
+            (false, 5, false),
+            (false, 6, true),
+            (false, 7, false),
+            (false, 26, false),
+            (true, 0, false),
+            (true, 4, false),
+            (true, 5, true),
+            (true, 6, true),
+            (true, 7, true),
+            (true, 8, false),
+            (true, 26, false),
+        ];
+        for &(center_alive, live_neighbors, next_alive) in &transitions {
+            assert_eq!(
+                Conway3D::next_alive(center_alive, live_neighbors),
+                next_alive,
+                "center_alive={} live_neighbors={}",
+                center_alive,
+                live_neighbors
+            );
+            let next_state = Conway3D::next_state(neighborhood(center_alive, live_neighbors));
+            assert_eq!(Conway3D::is_alive(next_state), next_alive);
+        }
+        // corner, edge and face neighbors all count
+        let mut state = neighborhood(false, 0);
+        state[0][0][0] = Conway3D::cell(true);
+        state[2][2][2] = Conway3D::cell(true);
+        state[0][1][1] = Conway3D::cell(true);
+        state[1][2][1] = Conway3D::cell(true);
+        state[1][1][0] = Conway3D::cell(true);
+        state[2][0][1] = Conway3D::cell(true);
+        assert_eq!(Conway3D::next_state(state), Conway3D::cell(true));
+    }
+
+    #[test]
+    fn test_leaf_from_bools() {
+        let mut hash_tables =
+            HashTables::<SyncTableEntry<(), NonZeroU32>, FxBuildHasher>::with_capacities(vec![
+                16;
+                2
+            ]);
+        hash_tables.get_mut::<Leaf>().enable_explicit_ids();
+        let empty_leaf = hash_tables
+            .get_or_insert_node::<Level1>([[[Conway3D::cell(false); 2]; 2]; 2])
+            .unwrap();
+        assert_eq!(
+            Conway3D::leaf_from_bools(&hash_tables, [[[false; 2]; 2]; 2]).unwrap(),
+            empty_leaf
+        );
+        let mut cells = [[[false; 2]; 2]; 2];
+        cells[0][0][1] = true;
+        cells[1][0][0] = true;
+        cells[1][1][1] = true;
+        let leaf = Conway3D::leaf_from_bools(&hash_tables, cells).unwrap();
+        assert_ne!(leaf, empty_leaf);
+        let (_, key, _) = hash_tables
+            .get_mut::<Leaf>()
+            .iter_with_ids()
+            .find(|&(id, _, _)| id == leaf.into())
+            .unwrap();
+        let key = Key::<Leaf>::from(key);
+        for (x, plane) in cells.iter().enumerate() {
+            for (y, row) in plane.iter().enumerate() {
+                for (z, &alive) in row.iter().enumerate() {
+                    assert_eq!(Conway3D::is_alive(key.child(x, y, z)), alive);
+                }
+            }
+        }
+        assert_eq!(hash_tables.stats()[0].len, 2);
+    }
+}